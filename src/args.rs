@@ -1,8 +1,16 @@
-use clap::{Parser, ValueEnum};
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum};
 use std::collections::HashSet;
+use std::io::{self, IsTerminal};
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::hash::Hash;
+use crate::selection::FileSelector;
+
+pub const DEFAULT_OUTPUT_FILE: &str = "output.txt";
+
+/// Output file name that stands for stdout.
+pub const STDOUT_OUTPUT: &str = "-";
 
 fn parse_set<T: FromStr + Eq + Hash>(s: &str) -> Result<HashSet<T>, String> {
     s.split(',')
@@ -22,7 +30,7 @@ pub struct Args {
     #[arg(short, long, value_name = "REPO_PATH", help = "Path to the directory to process (i.e., cloned repo). If no path is specified, defaults to the current directory.", default_value = ".")]
     pub repo_path: PathBuf,
 
-    #[arg(short, long, value_name = "OUTPUT_FILE", help = "Name for the output file. Defaults to \"output.txt\".", default_value = "output.txt")]
+    #[arg(short, long, value_name = "OUTPUT_FILE", help = "Name for the output file, or \"-\" to write to stdout. Defaults to \"output.txt\", or to stdout when it is not a terminal.", default_value = DEFAULT_OUTPUT_FILE)]
     pub output_file: String,
 
     #[arg(long, value_name = "IGNORE_FILES", help = "List of file names or patterns to ignore. Use glob patterns for wildcards.", default_value = "", value_parser = parse_set::<String>)]
@@ -31,11 +39,27 @@ pub struct Args {
     #[arg(long, value_name = "IGNORE_TYPES", help = "List of file extensions to ignore.", default_value = "", value_parser = parse_set::<String>)]
     pub ignore_types: HashSet<String>,
 
-    #[arg(long, value_name = "EXCLUDE_DIR", help = "List of directory names to exclude.", default_value = "node_modules,vendor,dist,build,target", value_parser = parse_set::<String>)]
+    #[arg(long, value_name = "EXCLUDE_DIR", help = "List of directories to exclude. A bare name such as \"build\" excludes directories of that name anywhere; an entry with a slash, such as \"crates/legacy/generated\" or \"/build\", is a path or glob pattern relative to the repository root.", default_value = "node_modules,vendor,dist,build,target", value_parser = parse_set::<String>)]
     pub exclude_dir: HashSet<String>,
 
-    #[arg(long, value_name = "INCLUDE_DIR", help = "Specific directory to include. Only contents of this directory will be documented.")]
-    pub include_dir: Option<PathBuf>,
+    /// Relative to the repository; main resolves them to canonical
+    /// repository-relative paths before walking.
+    #[arg(long, value_name = "INCLUDE_DIR", help = "Directory to include, relative to the repository path. Only the contents of the included directories, and the directories leading to them, will be documented. Repeat to include several directories.")]
+    pub include_dir: Vec<PathBuf>,
+
+    #[arg(long, value_name = "PATTERN", help = "Glob pattern of files to document, matched against the repository-relative path, e.g. \"src/**/*.rs\". Repeat for more patterns; a leading ! excludes the files it matches. The last matching pattern decides, and files no pattern matches are left out (unless the first pattern starts with !). The ignore options still apply.")]
+    pub include: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Match the glob patterns of --ignore-files, --ignore-types, --include, --allow-hidden and the configuration file regardless of case [possible values: true, false]",
+        action = clap::ArgAction::Set,
+        value_parser = clap::value_parser!(bool),
+        num_args = 0..=1,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
+    pub glob_case_insensitive: bool,
 
     #[arg(
         long,
@@ -59,8 +83,14 @@ pub struct Args {
     )]
     pub use_gitignore: bool,
 
-    #[arg(short, long, value_name = "FILE_PATH", help = "Path to a single file to process.")]
-    pub file_path: Option<PathBuf>,
+    #[arg(
+        short,
+        long,
+        value_name = "FILE_PATH",
+        value_parser = FileSelector::from_str,
+        help = "Document only this file instead of the repository. Repeat to document several files. Append :START-END (or :LINE, or :START-) to select lines, or #symbol to select the declaration of a function, type or Markdown heading (Outer::inner for nested ones); several selections of a file are merged and the lines in between are replaced by a note."
+    )]
+    pub file_path: Vec<FileSelector>,
 
     #[arg(long, value_name = "CONFIG_PATH", help = "Path to a custom configuration file. If not specified, the default configuration is used.")]
     pub config_path: Option<PathBuf>,
@@ -79,7 +109,7 @@ pub struct Args {
     #[arg(long, value_name = "MAX_DEPTH", help = "Maximum depth to traverse in the directory tree. Default is 100.", default_value_t = 100)]
     pub max_depth: usize,
 
-    #[arg(long, value_name = "FORMAT", help = "Output format: text, markdown, or html. Default is text.", value_enum, default_value_t = OutputFormat::Text)]
+    #[arg(long, value_name = "FORMAT", help = "Output format: text, markdown, html, json, jsonl (one JSON record per file, streamed), or xml (<documents> tags for LLM prompts). Default is text.", value_enum, default_value_t = OutputFormat::Text)]
     pub output_format: OutputFormat,
 
     #[arg(
@@ -92,15 +122,157 @@ pub struct Args {
         default_missing_value = "true"
     )]
     pub include_hidden: bool,
+
+    #[arg(long, value_name = "ALLOW_HIDDEN", help = "List of hidden paths to include even when hidden files are excluded, e.g. \".github/workflows/**,.cargo/config.toml\". Use glob patterns relative to the repository root. Matching files are kept even if --ignore-settings would drop them.", default_value = "", value_parser = parse_set::<String>)]
+    pub allow_hidden: HashSet<String>,
+
+    #[arg(long, value_name = "MODE", help = "How to handle files detected as binary: skip them, or list them with a placeholder such as \"[binary file, 48 KB, PNG image]\". Default is placeholder.", value_enum, default_value_t = BinaryMode::Placeholder)]
+    pub binary_files: BinaryMode,
+
+    #[arg(
+        long,
+        help = "Exit with code 3 if any path was skipped or had errors, after writing the document [possible values: true, false]",
+        action = clap::ArgAction::Set,
+        value_parser = clap::value_parser!(bool),
+        num_args = 0..=1,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
+    pub strict: bool,
+
+    #[arg(long, value_name = "ORDER", help = "Order of entries within each directory: dirs-first or alphabetical. Names are compared byte-wise so the output is identical on every filesystem. Default is dirs-first.", value_enum, default_value_t = SortOrder::DirsFirst)]
+    pub sort: SortOrder,
+
+    #[arg(
+        long,
+        help = "Count tokens and report the total and the largest files when done [possible values: true, false]",
+        action = clap::ArgAction::Set,
+        value_parser = clap::value_parser!(bool),
+        num_args = 0..=1,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
+    pub count_tokens: bool,
+
+    #[arg(long, value_name = "TOKENIZER", help = "Bundled BPE vocabulary used to count tokens: o200k or cl100k. Default is o200k.", value_enum, default_value_t = Tokenizer::O200k)]
+    pub tokenizer: Tokenizer,
+
+    #[arg(
+        long,
+        help = "Annotate each entry in the directory tree with its token count [possible values: true, false]",
+        action = clap::ArgAction::Set,
+        value_parser = clap::value_parser!(bool),
+        num_args = 0..=1,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
+    pub tree_tokens: bool,
+
+    #[arg(long, value_name = "MAX_TOKENS", help = "Token budget for the whole document. Files are chosen by priority (README, manifests, entry points); the rest are outlined, truncated or dropped and listed in a trailer.")]
+    pub max_tokens: Option<usize>,
+
+    #[arg(long, value_name = "TOKENS", conflicts_with_all = ["split_bytes", "file_path"], help = "Split the output into numbered parts (output.part1.txt, output.part2.txt, ...) of at most this many tokens each. Files are never split; part 1 holds the tree and an index of which files landed in which part.")]
+    pub split_tokens: Option<usize>,
+
+    #[arg(long, value_name = "BYTES", conflicts_with = "file_path", help = "Like --split-tokens, but limits the size of each part in bytes.")]
+    pub split_bytes: Option<usize>,
+
+    #[arg(long, value_name = "PATH", num_args = 0..=1, conflicts_with = "file_path", help = "Instead of writing the document, print on stdout whether each path is documented and which rule (exclude-dir, ignore-types, gitignore file and line, ...) excluded it. With a path, only that path is explained.")]
+    pub explain: Option<Option<PathBuf>>,
+
+    #[arg(
+        long,
+        conflicts_with = "file_path",
+        help = "List the files that would be documented with their size, lines and tokens, largest first, instead of writing the document [possible values: true, false]",
+        action = clap::ArgAction::Set,
+        value_parser = clap::value_parser!(bool),
+        num_args = 0..=1,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
+    pub dry_run: bool,
+
+    #[arg(
+        long,
+        help = "Add a statistics section before the file contents: file counts, code/comment/blank lines per language, total size, largest files and deepest paths [possible values: true, false]",
+        action = clap::ArgAction::Set,
+        value_parser = clap::value_parser!(bool),
+        num_args = 0..=1,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
+    pub stats: bool,
+
+    #[arg(
+        long,
+        help = "Prefix each line of file contents with its right-aligned line number in the file (text, markdown and xml); HTML shows them in a gutter [possible values: true, false]",
+        action = clap::ArgAction::Set,
+        value_parser = clap::value_parser!(bool),
+        num_args = 0..=1,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
+    pub line_numbers: bool,
+
+    #[arg(short, long, conflicts_with = "verbose", help = "Only print warnings and errors; no summary or progress.")]
+    pub quiet: bool,
+
+    #[arg(short, long, action = clap::ArgAction::Count, help = "Print more details on stderr: -v for each step, -vv for debug output.")]
+    pub verbose: u8,
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
 pub enum OutputFormat {
     Text,
     Markdown,
     HTML,
+    Json,
+    Jsonl,
+    Xml,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum BinaryMode {
+    Skip,
+    Placeholder,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum SortOrder {
+    DirsFirst,
+    Alphabetical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Tokenizer {
+    O200k,
+    Cl100k,
+}
+
+impl Args {
+    /// Whether the document is streamed to stdout instead of a file.
+    pub fn writes_to_stdout(&self) -> bool {
+        self.output_file == STDOUT_OUTPUT
+    }
 }
 
 pub fn parse_args() -> Args {
-    Args::parse()
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // Without an explicit output file, a pipe or redirect gets the document;
+    // split output always needs a file name for its parts, and --explain and
+    // --dry-run write no document at all
+    let output_defaulted = matches.value_source("output_file") == Some(ValueSource::DefaultValue);
+    let splitting = args.split_tokens.is_some() || args.split_bytes.is_some();
+    let writes_document = args.explain.is_none() && !args.dry_run;
+    if output_defaulted && !splitting && writes_document && !io::stdout().is_terminal() {
+        args.output_file = STDOUT_OUTPUT.to_string();
+        // Logging is not set up yet, so --quiet is checked here
+        if !args.quiet {
+            eprintln!("No --output-file given and stdout is not a terminal; writing the document to stdout");
+        }
+    }
+    args
 }
\ No newline at end of file
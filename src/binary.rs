@@ -0,0 +1,141 @@
+// binary.rs
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Number of leading bytes inspected when deciding whether a file is binary.
+const SAMPLE_SIZE: usize = 8 * 1024;
+
+/// Fraction of invalid UTF-8 bytes above which a sample is considered binary.
+const MAX_INVALID_UTF8_RATIO: f64 = 0.3;
+
+const MAGIC_NUMBERS: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "PNG image"),
+    (b"\xff\xd8\xff", "JPEG image"),
+    (b"GIF87a", "GIF image"),
+    (b"GIF89a", "GIF image"),
+    (b"II*\x00", "TIFF image"),
+    (b"MM\x00*", "TIFF image"),
+    (b"\x00\x00\x01\x00", "ICO image"),
+    (b"%PDF-", "PDF document"),
+    (b"PK\x03\x04", "ZIP archive"),
+    (b"PK\x05\x06", "ZIP archive"),
+    (b"\x1f\x8b", "gzip archive"),
+    (b"BZh", "bzip2 archive"),
+    (b"\xfd7zXZ\x00", "xz archive"),
+    (b"7z\xbc\xaf\x27\x1c", "7-Zip archive"),
+    (b"Rar!\x1a\x07", "RAR archive"),
+    (b"\x28\xb5\x2f\xfd", "zstd archive"),
+    (b"\x7fELF", "ELF executable"),
+    (b"MZ", "Windows executable"),
+    (b"\xcf\xfa\xed\xfe", "Mach-O executable"),
+    (b"\xce\xfa\xed\xfe", "Mach-O executable"),
+    (b"\xca\xfe\xba\xbe", "Java class or Mach-O universal binary"),
+    (b"\x00asm", "WebAssembly module"),
+    (b"SQLite format 3\x00", "SQLite database"),
+    (b"ID3", "MP3 audio"),
+    (b"OggS", "Ogg media"),
+    (b"fLaC", "FLAC audio"),
+    (b"wOFF", "WOFF font"),
+    (b"wOF2", "WOFF2 font"),
+];
+
+/// Description of a file that was detected as binary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinaryInfo {
+    pub size: u64,
+    pub description: &'static str,
+}
+
+impl fmt::Display for BinaryInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[binary file, {}, {}]", format_size(self.size), self.description)
+    }
+}
+
+/// Inspects the start of a file and returns `Some` if it looks binary.
+///
+/// Known magic numbers are checked first, then NUL bytes, then the share of
+/// bytes that are not valid UTF-8. Files starting with a UTF-16 byte order
+/// mark are treated as text.
+pub fn detect_binary(path: &Path) -> io::Result<Option<BinaryInfo>> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+
+    let mut sample = Vec::with_capacity(SAMPLE_SIZE);
+    file.by_ref().take(SAMPLE_SIZE as u64).read_to_end(&mut sample)?;
+
+    Ok(sniff(&sample).map(|description| BinaryInfo { size, description }))
+}
+
+fn sniff(sample: &[u8]) -> Option<&'static str> {
+    let has_binary_bytes = sample.contains(&0) || invalid_utf8_ratio(sample) > 0.0;
+
+    // Magic numbers made of printable ASCII (e.g. `MZ`, `ID3`) also start
+    // ordinary text files, so they only count alongside non-text bytes.
+    let magic = MAGIC_NUMBERS.iter()
+        .filter(|(magic, _)| sample.starts_with(magic))
+        .find(|(magic, _)| has_binary_bytes || !magic.iter().all(|b| b.is_ascii_graphic() || *b == b' '));
+    if let Some((_, description)) = magic {
+        return Some(description);
+    }
+    // `RIFF` and `ftyp` are printable too; the size fields around them are not
+    if has_binary_bytes && sample.len() >= 12 && sample.starts_with(b"RIFF") {
+        return Some(match &sample[8..12] {
+            b"WEBP" => "WebP image",
+            b"WAVE" => "WAV audio",
+            b"AVI " => "AVI video",
+            _ => "RIFF data",
+        });
+    }
+    if has_binary_bytes && sample.len() >= 8 && &sample[4..8] == b"ftyp" {
+        return Some("MP4 media");
+    }
+
+    if sample.starts_with(b"\xff\xfe") || sample.starts_with(b"\xfe\xff") {
+        return None;
+    }
+    if sample.contains(&0) {
+        return Some("data");
+    }
+
+    if invalid_utf8_ratio(sample) > MAX_INVALID_UTF8_RATIO {
+        return Some("data");
+    }
+    None
+}
+
+fn invalid_utf8_ratio(sample: &[u8]) -> f64 {
+    if sample.is_empty() {
+        return 0.0;
+    }
+
+    let mut invalid = 0;
+    let mut rest = sample;
+    while let Err(e) = std::str::from_utf8(rest) {
+        match e.error_len() {
+            Some(len) => {
+                invalid += len;
+                rest = &rest[e.valid_up_to() + len..];
+            }
+            // A multi-byte sequence cut off by the end of the sample
+            None => break,
+        }
+    }
+    invalid as f64 / sample.len() as f64
+}
+
+/// Formats a byte count as a short human readable size, e.g. `48 KB`.
+pub fn format_size(bytes: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = KB * 1024;
+    const GB: u64 = MB * 1024;
+
+    match bytes {
+        b if b < KB => format!("{} B", b),
+        b if b < MB => format!("{} KB", (b + KB / 2) / KB),
+        b if b < GB => format!("{:.1} MB", b as f64 / MB as f64),
+        b => format!("{:.1} GB", b as f64 / GB as f64),
+    }
+}
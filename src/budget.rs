@@ -0,0 +1,317 @@
+// budget.rs
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::args::{Args, OutputFormat};
+use crate::config::Config;
+use crate::language::{detect_language, is_declaration};
+use crate::tokens::{format_count, TokenCounter};
+use crate::utils::{relative_path_string, PathPattern};
+use crate::write::{escape_html, number_line, FileBody};
+
+/// Tokens set aside for section markers and the budget trailer.
+pub const BUDGET_RESERVE_TOKENS: usize = 200;
+
+/// Tokens of the note that ends a shortened file.
+const SHORTENED_NOTE_TOKENS: usize = 24;
+
+/// Shortened files smaller than this are dropped instead; a few lines of a
+/// file rarely help more than its entry in the trailer.
+const MIN_SHORTENED_TOKENS: usize = 32;
+
+/// How much of a file fits into the token budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fit {
+    Full,
+    /// Only the first `lines` lines are written.
+    Truncated { lines: usize, total_lines: usize },
+    /// Only declaration lines and headings are written.
+    Outlined { lines: usize, total_lines: usize },
+    Dropped,
+}
+
+impl Fit {
+    /// Short label used in the trailer and the JSON formats.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Fit::Full => "full",
+            Fit::Truncated { .. } => "truncated",
+            Fit::Outlined { .. } => "outlined",
+            Fit::Dropped => "dropped",
+        }
+    }
+}
+
+/// A file that was shortened or left out to stay under the budget.
+#[derive(Debug)]
+pub struct Reduction<'a> {
+    pub path: &'a Path,
+    pub fit: Fit,
+    /// Tokens of the whole file.
+    pub tokens: usize,
+}
+
+/// Decides, before anything is written, which files fit into `--max-tokens`.
+///
+/// Files are considered in priority order: files matching the configured
+/// `budget_priorities` patterns (earlier patterns first), then everything
+/// else; smaller files first within each group. Files that fit are written
+/// in full. The remaining budget then goes to outlines of the rest, and
+/// finally to truncated copies of files that have no outline. Counts are
+/// estimates of the rendered output; the summary reports the actual total.
+#[derive(Debug)]
+pub struct BudgetPlan {
+    max_tokens: usize,
+    fits: HashMap<PathBuf, (Fit, usize)>,
+    // Walk order, so the trailer lists files like the tree does
+    order: Vec<PathBuf>,
+}
+
+struct Candidate {
+    path: PathBuf,
+    rank: usize,
+    tokens: usize,
+    overhead: usize,
+    /// Token count of each line including its newline; empty for binary files.
+    line_tokens: Vec<usize>,
+    outline_tokens: Option<(usize, usize)>,
+}
+
+/// Gathers the cost of each walked file for a `BudgetPlan`, from contents
+/// that have already been read for the other passes over the files.
+pub struct BudgetPlanner<'a> {
+    args: &'a Args,
+    counter: &'a TokenCounter,
+    priorities: Vec<PathPattern>,
+    candidates: Vec<Candidate>,
+}
+
+impl<'a> BudgetPlanner<'a> {
+    pub fn new(args: &'a Args, config: &Config, counter: &'a TokenCounter) -> io::Result<Self> {
+        let priorities = compile_priorities(&config.budget_priorities, args.glob_case_insensitive)?;
+        Ok(BudgetPlanner { args, counter, priorities, candidates: Vec::new() })
+    }
+
+    /// Adds a walked file in walk order. `text_tokens` is the token count of
+    /// a text body as it is, if already known. Unreadable files (`body` is
+    /// `None`) are left for the writers to report.
+    pub fn add(&mut self, path: &Path, body: Option<&FileBody>, text_tokens: Option<usize>) {
+        let (args, counter) = (self.args, self.counter);
+        let relative_path = relative_path_string(path, &args.repo_path);
+        let overhead = file_overhead(args.output_format, counter.count(&relative_path));
+        let rank = priority_rank(&self.priorities, &relative_path);
+
+        let candidate = match body {
+            None => return,
+            Some(FileBody::Binary(info)) => Candidate {
+                path: path.to_path_buf(),
+                rank,
+                tokens: counter.count(&rendered(args.output_format, &info.to_string())),
+                overhead,
+                line_tokens: Vec::new(),
+                outline_tokens: None,
+            },
+            Some(body) => {
+                let text = body.text();
+                // With --line-numbers, lines are counted with their number
+                let width = text.lines().count().to_string().len();
+                let line_cost = |&(number, line): &(usize, &str)| {
+                    let line = match args.line_numbers {
+                        true => Cow::Owned(number_line(number, width, line)),
+                        false => Cow::Borrowed(line),
+                    };
+                    counter.count(&rendered(args.output_format, &line)) + 1
+                };
+                let line_tokens: Vec<usize> = text.lines().enumerate().map(|(i, line)| line_cost(&(i + 1, line))).collect();
+                let outline_tokens = outline_lines(path, &text)
+                    .map(|lines| (lines.len(), lines.iter().map(line_cost).sum()));
+                let tokens = match (args.line_numbers, rendered(args.output_format, &text), text_tokens) {
+                    (true, _, _) => line_tokens.iter().sum(),
+                    // Formats that write the text unescaped can reuse its count
+                    (false, Cow::Borrowed(_), Some(tokens)) => tokens,
+                    (false, rendered, _) => counter.count(&rendered),
+                };
+                Candidate {
+                    path: path.to_path_buf(),
+                    rank,
+                    tokens,
+                    overhead,
+                    line_tokens,
+                    outline_tokens,
+                }
+            },
+        };
+        self.candidates.push(candidate);
+    }
+
+    /// Plans the files added so far. `reserved` is the number of tokens
+    /// already spent on the header and the directory tree.
+    pub fn finish(self, max_tokens: usize, reserved: usize) -> BudgetPlan {
+        // Walk order, before the candidates are sorted by priority
+        let order: Vec<PathBuf> = self.candidates.iter().map(|c| c.path.clone()).collect();
+        let mut candidates = self.candidates;
+        candidates.sort_by(|a, b| a.rank.cmp(&b.rank).then(a.tokens.cmp(&b.tokens)));
+        let mut remaining = max_tokens.saturating_sub(reserved + BUDGET_RESERVE_TOKENS);
+        let mut fits: HashMap<PathBuf, (Fit, usize)> = HashMap::new();
+
+        // Whole files first
+        for candidate in &candidates {
+            if candidate.tokens + candidate.overhead <= remaining {
+                remaining -= candidate.tokens + candidate.overhead;
+                fits.insert(candidate.path.clone(), (Fit::Full, candidate.tokens));
+            }
+        }
+
+        // Then outlines, which keep the shape of a file at a fraction of its size
+        for candidate in &candidates {
+            if fits.contains_key(&candidate.path) {
+                continue;
+            }
+            let Some((lines, tokens)) = candidate.outline_tokens else {
+                continue;
+            };
+            let cost = tokens + candidate.overhead + SHORTENED_NOTE_TOKENS;
+            if tokens >= MIN_SHORTENED_TOKENS && cost <= remaining {
+                remaining -= cost;
+                let total_lines = candidate.line_tokens.len();
+                fits.insert(candidate.path.clone(), (Fit::Outlined { lines, total_lines }, candidate.tokens));
+            }
+        }
+
+        // Then the start of whatever is left, while budget remains
+        for candidate in &candidates {
+            if fits.contains_key(&candidate.path) {
+                continue;
+            }
+            let available = remaining.saturating_sub(candidate.overhead + SHORTENED_NOTE_TOKENS);
+            let (mut lines, mut kept_tokens) = (0, 0);
+            for &tokens in &candidate.line_tokens {
+                if kept_tokens + tokens > available {
+                    break;
+                }
+                lines += 1;
+                kept_tokens += tokens;
+            }
+
+            let fit = if kept_tokens >= MIN_SHORTENED_TOKENS {
+                remaining -= kept_tokens + candidate.overhead + SHORTENED_NOTE_TOKENS;
+                Fit::Truncated { lines, total_lines: candidate.line_tokens.len() }
+            } else {
+                Fit::Dropped
+            };
+            fits.insert(candidate.path.clone(), (fit, candidate.tokens));
+        }
+
+        BudgetPlan { max_tokens, fits, order }
+    }
+}
+
+impl BudgetPlan {
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    /// How much of a file fits. Files the plan does not know are written in full.
+    pub fn fit(&self, path: &Path) -> Fit {
+        self.fits.get(path).map(|(fit, _)| *fit).unwrap_or(Fit::Full)
+    }
+
+    /// Shortens a file body according to the plan, ending it with a note on
+    /// what was left out. Returns `None` for dropped files.
+    pub fn apply(&self, path: &Path, body: FileBody) -> Option<FileBody> {
+        match (self.fit(path), body) {
+            (Fit::Dropped, _) => None,
+            (Fit::Truncated { lines, total_lines }, FileBody::Text(text)) => Some(FileBody::Excerpt(
+                text.lines().take(lines).enumerate()
+                    .map(|(i, line)| (Some(i + 1), line.to_string()))
+                    .chain([(None, format!("[truncated to fit the token budget: first {} of {} lines shown]", lines, total_lines))])
+                    .collect(),
+            )),
+            (Fit::Outlined { lines, total_lines }, FileBody::Text(text)) => Some(FileBody::Excerpt(
+                outline_lines(path, &text)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(number, line)| (Some(number), line.to_string()))
+                    .chain([(None, format!("[outline to fit the token budget: {} of {} lines shown]", lines, total_lines))])
+                    .collect(),
+            )),
+            (_, body) => Some(body),
+        }
+    }
+
+    /// Files that were shortened or dropped, in walk order.
+    pub fn reductions(&self) -> Vec<Reduction<'_>> {
+        self.order.iter()
+            .filter_map(|path| {
+                let (fit, tokens) = self.fits.get(path)?;
+                (*fit != Fit::Full).then_some(Reduction { path, fit: *fit, tokens: *tokens })
+            })
+            .collect()
+    }
+
+    /// One line per shortened or dropped file, e.g. `src/big.rs (dropped, 12,345 tokens)`.
+    pub fn trailer_rows(&self, root: &Path) -> Vec<String> {
+        self.reductions().iter()
+            .map(|r| format!("{} ({}, {} tokens)", relative_path_string(r.path, root), r.fit.label(), format_count(r.tokens)))
+            .collect()
+    }
+}
+
+fn compile_priorities(patterns: &[String], case_insensitive: bool) -> io::Result<Vec<PathPattern>> {
+    patterns.iter()
+        .map(|pattern| {
+            PathPattern::new(pattern, case_insensitive)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid budget priority pattern '{}': {}", pattern, e)))
+        })
+        .collect()
+}
+
+/// Index of the first matching priority pattern, or the number of patterns if
+/// none match.
+fn priority_rank(priorities: &[PathPattern], relative_path: &str) -> usize {
+    priorities.iter()
+        .position(|pattern| pattern.is_match_within(relative_path, false))
+        .unwrap_or(priorities.len())
+}
+
+/// Text as the selected format writes it; HTML and JSON escape it.
+fn rendered(format: OutputFormat, text: &str) -> Cow<'_, str> {
+    match format {
+        OutputFormat::HTML => Cow::Owned(escape_html(text)),
+        OutputFormat::Json | OutputFormat::Jsonl => Cow::Owned(serde_json::to_string(text).unwrap_or_default()),
+        _ => Cow::Borrowed(text),
+    }
+}
+
+/// Estimated tokens for the markers written around a file's content.
+fn file_overhead(format: OutputFormat, path_tokens: usize) -> usize {
+    match format {
+        OutputFormat::Text => 12 + 2 * path_tokens,
+        OutputFormat::Markdown => 12 + path_tokens,
+        OutputFormat::HTML => 32 + 3 * path_tokens,
+        OutputFormat::Xml => 24 + path_tokens,
+        // The SHA-256 alone takes around 40 tokens
+        OutputFormat::Json | OutputFormat::Jsonl => 80 + path_tokens,
+    }
+}
+
+
+/// Returns the lines that outline a file, with their 1-based line numbers:
+/// declarations in source code and headings in Markdown. Files without a
+/// known language have no outline.
+fn outline_lines<'a>(path: &Path, text: &'a str) -> Option<Vec<(usize, &'a str)>> {
+    let language = detect_language(path, text)?;
+    let numbered = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+    let lines: Vec<(usize, &str)> = if language == "markdown" {
+        numbered.filter(|(_, line)| line.starts_with('#')).collect()
+    } else {
+        numbered
+            .filter(|(_, line)| {
+                let trimmed = line.trim_start();
+                trimmed.starts_with('@') || is_declaration(trimmed)
+            })
+            .collect()
+    };
+    (!lines.is_empty()).then_some(lines)
+}
@@ -15,6 +15,12 @@ pub struct Config {
     #[allow(dead_code)]
     #[serde(default = "default_max_depth")]
     pub max_depth: usize,
+
+    #[serde(default)]
+    pub hidden_allowlist: HashSet<String>,
+
+    #[serde(default = "default_budget_priorities")]
+    pub budget_priorities: Vec<String>,
 }
 
 fn default_settings_extensions() -> HashSet<String> {
@@ -33,6 +39,14 @@ fn default_ignore_types() -> HashSet<String> {
     ].iter().map(|s| s.to_string()).collect()
 }
 
+fn default_budget_priorities() -> Vec<String> {
+    [
+        "README*",
+        "Cargo.toml", "package.json", "pyproject.toml", "setup.py", "go.mod", "pom.xml", "build.gradle", "Gemfile", "CMakeLists.txt", "Makefile",
+        "main.*", "lib.rs", "index.*", "app.*", "__main__.py",
+    ].iter().map(|s| s.to_string()).collect()
+}
+
 fn default_max_depth() -> usize {
     100
 }
\ No newline at end of file
@@ -0,0 +1,306 @@
+// document.rs
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use walkdir::DirEntry;
+use crate::args::Args;
+use crate::budget::{BudgetPlan, Fit};
+use crate::errors::SkipLog;
+use crate::language::detect_language;
+use crate::logging;
+use crate::selection::SelectedFile;
+use crate::stats::RepoStats;
+use crate::utils::relative_path_string;
+use crate::write::{read_file_body, FileBody};
+
+/// Version of the JSON document layout, bumped on incompatible changes.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The whole repository as a single serializable document.
+#[derive(Debug, Serialize)]
+pub struct Document {
+    pub schema_version: u32,
+    pub repository: RepositoryInfo,
+    /// Absent in single file mode.
+    pub tree: Option<TreeNode>,
+    pub files: Vec<FileRecord>,
+    pub skipped: Vec<SkippedRecord>,
+    /// Present only with `--max-tokens`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<BudgetRecord>,
+    /// Present only with `--stats`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statistics: Option<RepoStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepositoryInfo {
+    /// Name of the repository directory, or of the file in single file mode.
+    pub name: String,
+    /// Path as given on the command line.
+    pub path: String,
+    pub generator: &'static str,
+    pub file_count: usize,
+    pub total_size: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TreeNode {
+    pub name: String,
+    /// Repository-relative path with forward slashes; empty for the root.
+    pub path: String,
+    #[serde(rename = "type")]
+    pub kind: NodeKind,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreeNode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    Directory,
+    File,
+    Symlink,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileRecord {
+    /// Repository-relative path with forward slashes.
+    pub path: String,
+    /// Size on disk in bytes.
+    pub size: u64,
+    /// Number of lines, or null for binary files.
+    pub lines: Option<usize>,
+    pub language: Option<&'static str>,
+    /// Hex encoded SHA-256 of the bytes on disk.
+    pub sha256: String,
+    /// Decoded text, or null for binary files.
+    pub content: Option<String>,
+    /// Detected kind of binary file, e.g. "PNG image"; null for text files.
+    pub binary: Option<&'static str>,
+    /// "truncated" or "outlined" if the content was shortened to fit
+    /// `--max-tokens`; absent otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<&'static str>,
+    /// 1-based inclusive line ranges selected with `--file-path`; absent
+    /// when the whole file is included.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ranges: Option<Vec<[usize; 2]>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkippedRecord {
+    pub path: String,
+    pub reason: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BudgetRecord {
+    pub max_tokens: usize,
+    /// Files that were shortened or dropped.
+    pub reduced: Vec<ReducedRecord>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReducedRecord {
+    pub path: String,
+    /// "truncated", "outlined" or "dropped".
+    pub action: &'static str,
+    /// Tokens of the whole file.
+    pub tokens: usize,
+}
+
+/// One line of `--output-format jsonl`, tagged with a `type` field: the
+/// statistics, if any, a record per file, then the budget and skipped records.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum JsonlRecord {
+    Stats(RepoStats),
+    File(FileRecord),
+    Budget(BudgetRecord),
+    Skipped(SkippedRecord),
+}
+
+/// Builds the document for the walked entries of a repository, shortening or
+/// leaving out files as planned by `budget`.
+pub fn build_document(entries: &[DirEntry], args: &Args, budget: Option<&BudgetPlan>, skipped: &mut SkipLog) -> Document {
+    let files = entries.iter()
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let path = e.path();
+            let body = read_file_body(path, skipped)?;
+            logging::file_written(path);
+            let (body, fit) = match budget {
+                Some(plan) => (plan.apply(path, body)?, plan.fit(path)),
+                None => (body, Fit::Full),
+            };
+            file_record_from_body(path, &relative_path_string(path, &args.repo_path), body, fit)
+                .map_err(|e| skipped.record_io(path, &e))
+                .ok()
+        })
+        .collect();
+
+    let name = entries.first()
+        .map(|root| root.file_name().to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut document = finish_document(name, args.repo_path.display().to_string(), build_tree(entries, args), files, skipped, args);
+    document.budget = budget.map(|plan| budget_record(plan, &args.repo_path));
+    document
+}
+
+/// Builds the document for the files selected in single file mode. A single
+/// file names the document and its record; several files are named by their
+/// paths as given, under the current directory.
+pub fn build_selection_document(selected: &[SelectedFile], args: &Args, skipped: &mut SkipLog) -> Document {
+    let file_name = |path: &Path| path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let (name, path) = match selected {
+        [file] => (file_name(&file.path), file.path.display().to_string()),
+        _ => (std::env::current_dir().map(|dir| file_name(&dir)).unwrap_or_default(), ".".to_string()),
+    };
+
+    let files = selected.iter()
+        .filter_map(|file| {
+            let body = read_file_body(&file.path, skipped)?;
+            let record_path = if selected.len() == 1 { file_name(&file.path) } else { file.path.display().to_string() };
+            let mut record = file_record_from_body(&file.path, &record_path, file.excerpt(body), Fit::Full)
+                .map_err(|e| skipped.record_io(&file.path, &e))
+                .ok()?;
+            record.ranges = file.ranges.as_ref().map(|ranges| ranges.iter().map(|&(start, end)| [start, end]).collect());
+            Some(record)
+        })
+        .collect();
+    finish_document(name, path, None, files, skipped, args)
+}
+
+fn finish_document(name: String, path: String, tree: Option<TreeNode>, files: Vec<FileRecord>, skipped: &SkipLog, args: &Args) -> Document {
+    Document {
+        schema_version: SCHEMA_VERSION,
+        repository: RepositoryInfo {
+            name,
+            path,
+            generator: "repo2txt",
+            file_count: files.len(),
+            total_size: files.iter().map(|f| f.size).sum(),
+        },
+        tree,
+        files,
+        skipped: skipped_records(skipped, &args.repo_path),
+        budget: None,
+        statistics: None,
+    }
+}
+
+/// Builds a record from a file body that has already been read, and shortened
+/// to `fit` if need be; only the hash needs another pass over the file.
+pub fn file_record_from_body(path: &Path, relative_path: &str, body: FileBody, fit: Fit) -> io::Result<FileRecord> {
+    let budget = (fit != Fit::Full).then(|| fit.label());
+    let sha256 = sha256_file(path)?;
+
+    Ok(match body {
+        FileBody::Binary(info) => FileRecord {
+            path: relative_path.to_string(),
+            size: info.size,
+            lines: None,
+            language: None,
+            sha256,
+            content: None,
+            binary: Some(info.description),
+            budget,
+            ranges: None,
+        },
+        body => {
+            let text = body.into_text();
+            FileRecord {
+                path: relative_path.to_string(),
+                size: path.metadata().map(|m| m.len()).unwrap_or(0),
+                lines: Some(text.lines().count()),
+                language: detect_language(path, &text),
+                sha256,
+                content: Some(text),
+                binary: None,
+                budget,
+                ranges: None,
+            }
+        },
+    })
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Nests walk (pre-)order entries into a tree rooted at the first entry.
+pub fn build_tree(entries: &[DirEntry], args: &Args) -> Option<TreeNode> {
+    // Nodes whose children are still being collected, with their depth
+    let mut stack: Vec<(usize, TreeNode)> = Vec::new();
+
+    for entry in entries {
+        while stack.len() > 1 && stack.last().is_some_and(|(depth, _)| *depth >= entry.depth()) {
+            close_node(&mut stack);
+        }
+
+        let kind = if entry.file_type().is_dir() {
+            NodeKind::Directory
+        } else if entry.file_type().is_symlink() {
+            NodeKind::Symlink
+        } else {
+            NodeKind::File
+        };
+        stack.push((entry.depth(), TreeNode {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            path: relative_path_string(entry.path(), &args.repo_path),
+            kind,
+            children: Vec::new(),
+        }));
+    }
+
+    while stack.len() > 1 {
+        close_node(&mut stack);
+    }
+    stack.pop().map(|(_, root)| root)
+}
+
+/// Moves the innermost open node into its parent's children.
+fn close_node(stack: &mut Vec<(usize, TreeNode)>) {
+    if let Some((_, node)) = stack.pop() {
+        if let Some((_, parent)) = stack.last_mut() {
+            parent.children.push(node);
+        }
+    }
+}
+
+pub fn budget_record(plan: &BudgetPlan, root: &Path) -> BudgetRecord {
+    BudgetRecord {
+        max_tokens: plan.max_tokens(),
+        reduced: plan.reductions().iter()
+            .map(|r| ReducedRecord {
+                path: relative_path_string(r.path, root),
+                action: r.fit.label(),
+                tokens: r.tokens,
+            })
+            .collect(),
+    }
+}
+
+pub fn skipped_records(skipped: &SkipLog, root: &Path) -> Vec<SkippedRecord> {
+    skipped.entries().iter()
+        .map(|entry| SkippedRecord {
+            path: relative_path_string(&entry.path, root),
+            reason: entry.reason.to_string(),
+            message: entry.message.clone(),
+        })
+        .collect()
+}
@@ -0,0 +1,77 @@
+// dry_run.rs
+use std::io::{self, Write};
+use std::path::Path;
+use walkdir::DirEntry;
+use crate::args::Args;
+use crate::binary::format_size;
+use crate::errors::SkipLog;
+use crate::tokens::{format_count, TokenCounter};
+use crate::utils::relative_path_string;
+use crate::write::{read_file_body, FileBody};
+
+/// A file that would be documented, as listed by `--dry-run`.
+struct ListedFile {
+    path: String,
+    size: u64,
+    // None for binary files
+    lines: Option<usize>,
+    tokens: Option<usize>,
+    binary: Option<&'static str>,
+}
+
+/// Lists the files that would be documented with their size, line count and
+/// token count, largest first, followed by the totals. Nothing is written
+/// to the output file; unreadable files are recorded in `skipped`.
+pub fn write_dry_run<W: Write>(out: &mut W, entries: &[DirEntry], args: &Args, counter: &TokenCounter, skipped: &mut SkipLog) -> io::Result<()> {
+    let mut files: Vec<ListedFile> = entries.iter()
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|entry| list_file(entry.path(), args, counter, skipped))
+        .collect();
+    files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+
+    writeln!(out, "{:>10}  {:>10}  {:>10}  PATH", "SIZE", "LINES", "TOKENS")?;
+    for file in &files {
+        let lines = file.lines.map(format_count).unwrap_or_else(|| "-".to_string());
+        let tokens = file.tokens.map(format_count).unwrap_or_else(|| "-".to_string());
+        write!(out, "{:>10}  {:>10}  {:>10}  {}", format_size(file.size), lines, tokens, file.path)?;
+        match &file.binary {
+            Some(description) => writeln!(out, " (binary, {})", description)?,
+            None => writeln!(out)?,
+        }
+    }
+
+    let size: u64 = files.iter().map(|f| f.size).sum();
+    let lines: usize = files.iter().filter_map(|f| f.lines).sum();
+    let tokens: usize = files.iter().filter_map(|f| f.tokens).sum();
+    writeln!(
+        out,
+        "Total: {} files, {}, {} lines, {} tokens ({})",
+        format_count(files.len()), format_size(size), format_count(lines), format_count(tokens), counter.name(),
+    )
+}
+
+fn list_file(path: &Path, args: &Args, counter: &TokenCounter, skipped: &mut SkipLog) -> Option<ListedFile> {
+    let size = match path.metadata() {
+        Ok(metadata) => metadata.len(),
+        Err(e) => {
+            skipped.record_io(path, &e);
+            return None;
+        }
+    };
+    let mut file = ListedFile {
+        path: relative_path_string(path, &args.repo_path),
+        size,
+        lines: None,
+        tokens: None,
+        binary: None,
+    };
+    match read_file_body(path, skipped)? {
+        FileBody::Binary(info) => file.binary = Some(info.description),
+        body => {
+            let text = body.text();
+            file.lines = Some(text.lines().count());
+            file.tokens = Some(counter.count(&text));
+        }
+    }
+    Some(file)
+}
@@ -0,0 +1,61 @@
+// encoding.rs
+use std::fs;
+use std::io;
+use std::path::Path;
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+
+/// Text decoded from a file, along with how it was decoded.
+pub struct DecodedText {
+    pub text: String,
+    pub encoding: &'static str,
+    /// True if some bytes could not be decoded and were replaced with U+FFFD.
+    pub lossy: bool,
+}
+
+/// Reads a file as text without ever failing on its encoding.
+///
+/// A byte order mark selects UTF-8, UTF-16LE or UTF-16BE. Without one, valid
+/// UTF-8 is used as is. Invalid UTF-8 is treated as Windows-1252 (a superset of
+/// Latin-1) unless the file also contains valid multi-byte UTF-8 sequences, in
+/// which case it is most likely damaged UTF-8 and is decoded lossily.
+pub fn read_text_file(path: &Path) -> io::Result<DecodedText> {
+    let bytes = fs::read(path)?;
+    Ok(decode(&bytes))
+}
+
+pub fn decode(bytes: &[u8]) -> DecodedText {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        return decode_with(encoding, &bytes[bom_len..]);
+    }
+
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return DecodedText { text: text.to_string(), encoding: UTF_8.name(), lossy: false };
+    }
+
+    if contains_multibyte_utf8(bytes) {
+        decode_with(UTF_8, bytes)
+    } else {
+        decode_with(WINDOWS_1252, bytes)
+    }
+}
+
+fn decode_with(encoding: &'static Encoding, bytes: &[u8]) -> DecodedText {
+    let (text, lossy) = encoding.decode_without_bom_handling(bytes);
+    DecodedText { text: text.into_owned(), encoding: encoding.name(), lossy }
+}
+
+fn contains_multibyte_utf8(bytes: &[u8]) -> bool {
+    let mut rest = bytes;
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => return !valid.is_ascii(),
+            Err(e) => {
+                if !rest[..e.valid_up_to()].is_ascii() {
+                    return true;
+                }
+                let invalid_len = e.error_len().unwrap_or(rest.len() - e.valid_up_to());
+                rest = &rest[e.valid_up_to() + invalid_len..];
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+// errors.rs
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::logging::warning;
+
+/// Exit code used with `--strict` when any path was skipped or had errors.
+pub const EXIT_SKIPPED: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SkipReason {
+    PermissionDenied,
+    BrokenSymlink,
+    SymlinkLoop,
+    Unreadable,
+    DecodeError,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            SkipReason::PermissionDenied => "permission denied",
+            SkipReason::BrokenSymlink => "broken symlink",
+            SkipReason::SymlinkLoop => "symlink loop",
+            SkipReason::Unreadable => "unreadable",
+            SkipReason::DecodeError => "decode error",
+        };
+        f.write_str(label)
+    }
+}
+
+/// A path that was left out of the document, or only partially documented.
+#[derive(Debug, Clone)]
+pub struct SkippedPath {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+    pub message: String,
+}
+
+/// Problems collected during a run so that a single bad path never aborts
+/// the whole document.
+#[derive(Debug, Default)]
+pub struct SkipLog {
+    entries: Vec<SkippedPath>,
+}
+
+impl SkipLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, path: &Path, reason: SkipReason, message: impl Into<String>) {
+        self.entries.push(SkippedPath {
+            path: path.to_path_buf(),
+            reason,
+            message: message.into(),
+        });
+    }
+
+    /// Records an I/O error, classifying permission problems separately.
+    pub fn record_io(&mut self, path: &Path, error: &io::Error) {
+        let reason = match error.kind() {
+            io::ErrorKind::PermissionDenied => SkipReason::PermissionDenied,
+            _ => SkipReason::Unreadable,
+        };
+        self.record(path, reason, error.to_string());
+    }
+
+    /// Records an error reported while walking the directory tree.
+    pub fn record_walk(&mut self, error: &walkdir::Error) {
+        let path = error.path().unwrap_or(Path::new(""));
+        if error.loop_ancestor().is_some() {
+            self.record(path, SkipReason::SymlinkLoop, error.to_string());
+        } else if let Some(io_error) = error.io_error() {
+            if io_error.kind() == io::ErrorKind::NotFound && path.is_symlink() {
+                self.record(path, SkipReason::BrokenSymlink, "target does not exist");
+            } else {
+                self.record_io(path, io_error);
+            }
+        } else {
+            self.record(path, SkipReason::Unreadable, error.to_string());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn entries(&self) -> &[SkippedPath] {
+        &self.entries
+    }
+
+    /// Prints a short summary of the collected problems to stderr.
+    pub fn print_summary(&self, root: &Path) {
+        if self.is_empty() {
+            return;
+        }
+
+        let lines: Vec<String> = self.entries.iter()
+            .map(|entry| {
+                let path = entry.path.strip_prefix(root).unwrap_or(&entry.path);
+                format!("  {}: {}: {}", path.display(), entry.reason, entry.message)
+            })
+            .collect();
+        warning!("{} path(s) were skipped or had errors:\n{}", self.len(), lines.join("\n"));
+    }
+}
@@ -0,0 +1,168 @@
+// explain.rs
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+use crate::args::Args;
+use crate::config::Config;
+use crate::gitignore::{GitignoreFilter, GitignoreRule};
+use crate::utils::{compare_entries, ignore_reason, relative_path_string, HiddenAllowlist, IgnoreGlobs};
+
+/// The rule that kept a path out of the document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IgnoreReason {
+    OutputFile,
+    OutputPart,
+    OutsideIncludeDir(Vec<PathBuf>),
+    MaxDepth(usize),
+    GitDir,
+    Hidden,
+    ExcludeDir(String),
+    IgnoreFile(String),
+    IgnoreType(String),
+    /// The deciding `--include` pattern, or `None` if none matched.
+    NotIncluded(Option<String>),
+    Settings(String),
+    Binary(String),
+    Pattern { option: &'static str, pattern: String },
+    Gitignore(GitignoreRule),
+}
+
+impl fmt::Display for IgnoreReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IgnoreReason::OutputFile => write!(f, "the output file itself"),
+            IgnoreReason::OutputPart => write!(f, "a part of the split output file"),
+            IgnoreReason::OutsideIncludeDir(dirs) => {
+                let dirs: Vec<String> = dirs.iter().map(|dir| dir.display().to_string()).collect();
+                write!(f, "outside --include-dir {}", dirs.join(", "))
+            }
+            IgnoreReason::MaxDepth(depth) => write!(f, "deeper than --max-depth {}", depth),
+            IgnoreReason::GitDir => write!(f, ".git is never documented"),
+            IgnoreReason::Hidden => write!(f, "hidden (see --include-hidden and --allow-hidden)"),
+            IgnoreReason::ExcludeDir(name) => write!(f, "--exclude-dir \"{}\"", name),
+            IgnoreReason::IgnoreFile(name) => write!(f, "--ignore-files \"{}\"", name),
+            IgnoreReason::IgnoreType(ext) => write!(f, "--ignore-types \"{}\"", ext),
+            IgnoreReason::NotIncluded(Some(pattern)) => write!(f, "--include pattern \"{}\"", pattern),
+            IgnoreReason::NotIncluded(None) => write!(f, "matched by no --include pattern"),
+            IgnoreReason::Settings(ext) => write!(f, "settings file with extension \"{}\" (see --ignore-settings)", ext),
+            IgnoreReason::Binary(kind) => write!(f, "binary file, {} (--binary-files skip)", kind),
+            IgnoreReason::Pattern { option, pattern } => write!(f, "{} pattern \"{}\"", option, pattern),
+            IgnoreReason::Gitignore(rule) => write!(f, "{}", rule),
+        }
+    }
+}
+
+/// Walks the repository like `walk_entries` and writes one line per path
+/// saying whether it is documented and, if not, which rule excluded it.
+/// Excluded directories are reported once, without their contents.
+///
+/// With `target`, only that path is reported; if one of its parent
+/// directories is excluded, that directory and its rule are named instead.
+#[allow(clippy::too_many_arguments)]
+pub fn explain_paths<W: Write>(
+    out: &mut W,
+    root: &Path,
+    target: Option<&Path>,
+    args: &Args,
+    config: &Config,
+    globs: &IgnoreGlobs,
+    hidden_allowlist: &HiddenAllowlist,
+    output_file_path: &Path
+) -> io::Result<()> {
+    let mut gitignore = args.use_gitignore.then(|| GitignoreFilter::new(root));
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let target = target.map(|target| root.join(repository_relative(target, &canonical_root)));
+    let sort = args.sort;
+
+    // One level past the depth limit, so that the limit itself can be reported
+    let mut walker = WalkDir::new(root)
+        .min_depth(1)
+        .max_depth(args.max_depth.saturating_add(1))
+        .follow_links(args.follow_symlinks)
+        .sort_by(move |a, b| compare_entries(a, b, sort))
+        .into_iter();
+
+    while let Some(result) = walker.next() {
+        let entry = match result {
+            Ok(entry) => entry,
+            Err(e) => {
+                let path = e.path().map(|p| relative_path_string(p, root)).unwrap_or_default();
+                writeln!(out, "{}: skipped: {}", path, e)?;
+                continue;
+            }
+        };
+        let is_dir = entry.file_type().is_dir();
+        if target.as_ref().is_some_and(|target| !target.starts_with(entry.path())) {
+            if is_dir {
+                walker.skip_current_dir();
+            }
+            continue;
+        }
+
+        let reason = ignore_reason(&entry, globs, hidden_allowlist, args, config, output_file_path)
+            .or_else(|| gitignore.as_mut().and_then(|g| g.matched(&entry)).map(IgnoreReason::Gitignore))
+            .map(|reason| relative_gitignore(reason, &canonical_root));
+        let path = display_path(&entry, root);
+
+        match (&target, reason) {
+            (Some(target), Some(reason)) if target != entry.path() => {
+                let target = relative_path_string(target, root);
+                return writeln!(out, "{}: excluded: parent {} is excluded: {}", target, path, reason);
+            }
+            (_, Some(reason)) => {
+                writeln!(out, "{}: excluded: {}", path, reason)?;
+                if is_dir {
+                    walker.skip_current_dir();
+                }
+            }
+            (_, None) if entry.path_is_symlink() && !entry.path().exists() => {
+                writeln!(out, "{}: skipped: broken symlink", path)?;
+            }
+            (Some(target), None) if target != entry.path() => {}
+            (_, None) => writeln!(out, "{}: included", path)?,
+        }
+
+        if target.as_ref().is_some_and(|target| target == entry.path()) {
+            return Ok(());
+        }
+    }
+
+    match target {
+        Some(target) => Err(io::Error::new(io::ErrorKind::NotFound, format!("The path to explain is not in the repository: {}", target.display()))),
+        None => Ok(()),
+    }
+}
+
+/// Turns a path given on the command line into one relative to the
+/// repository; relative paths already are.
+fn repository_relative(target: &Path, canonical_root: &Path) -> PathBuf {
+    if target.is_absolute() {
+        if let Ok(relative) = target.canonicalize().unwrap_or_else(|_| target.to_path_buf()).strip_prefix(canonical_root) {
+            return relative.to_path_buf();
+        }
+    }
+    target.components().filter(|c| !matches!(c, Component::CurDir)).collect()
+}
+
+/// Shows gitignore files relative to the repository when they are inside it.
+fn relative_gitignore(reason: IgnoreReason, canonical_root: &Path) -> IgnoreReason {
+    match reason {
+        IgnoreReason::Gitignore(mut rule) => {
+            if let Some(relative) = rule.file.as_ref().and_then(|file| file.strip_prefix(canonical_root).ok()) {
+                rule.file = Some(relative.to_path_buf());
+            }
+            IgnoreReason::Gitignore(rule)
+        }
+        reason => reason,
+    }
+}
+
+fn display_path(entry: &DirEntry, root: &Path) -> String {
+    let path = relative_path_string(entry.path(), root);
+    if entry.file_type().is_dir() {
+        format!("{}/", path)
+    } else {
+        path
+    }
+}
@@ -0,0 +1,157 @@
+// gitignore.rs
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use ignore::gitignore::{gitconfig_excludes_path, Glob, Gitignore, GitignoreBuilder};
+use ignore::Match;
+use crate::logging::warning;
+use walkdir::DirEntry;
+
+/// Applies gitignore rules the way git does while walking a repository.
+///
+/// `.gitignore` files are loaded lazily for every directory that is visited and
+/// only apply to paths below that directory, with deeper files taking
+/// precedence. When the repository lives inside a git work tree, the
+/// `.gitignore` files between the work tree root and the repository path,
+/// `.git/info/exclude` and the user's `core.excludesFile` are consulted as well.
+pub struct GitignoreFilter {
+    root: PathBuf,
+    canonical_root: PathBuf,
+    dirs: HashMap<PathBuf, Option<Gitignore>>,
+    parents: Vec<Gitignore>,
+    exclude: Option<Gitignore>,
+    global: Option<Gitignore>,
+}
+
+impl GitignoreFilter {
+    pub fn new(root: &Path) -> Self {
+        let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let mut parents = Vec::new();
+        let mut exclude = None;
+        let mut global = None;
+
+        if let Some(git_root) = find_git_root(&canonical_root) {
+            // Parent .gitignore files, nearest first; the repository root itself
+            // is loaded lazily along with every other walked directory.
+            for dir in canonical_root.ancestors().skip(1) {
+                if !dir.starts_with(&git_root) {
+                    break;
+                }
+                if let Some(gitignore) = load_gitignore(dir, &dir.join(".gitignore")) {
+                    parents.push(gitignore);
+                }
+            }
+
+            exclude = load_gitignore(&git_root, &git_root.join(".git").join("info").join("exclude"));
+            global = gitconfig_excludes_path()
+                .and_then(|path| load_gitignore(&git_root, &path));
+        }
+
+        GitignoreFilter {
+            root: root.to_path_buf(),
+            canonical_root,
+            dirs: HashMap::new(),
+            parents,
+            exclude,
+            global,
+        }
+    }
+
+    /// Returns true if the entry is ignored by the closest matching rule.
+    pub fn is_ignored(&mut self, entry: &DirEntry) -> bool {
+        self.matched(entry).is_some()
+    }
+
+    /// Returns the rule that ignores the entry, if any.
+    pub fn matched(&mut self, entry: &DirEntry) -> Option<GitignoreRule> {
+        let relative = match entry.path().strip_prefix(&self.root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => relative.to_path_buf(),
+            _ => return None,
+        };
+        let candidate = self.canonical_root.join(&relative);
+        let is_dir = entry.file_type().is_dir();
+
+        // Walk from the entry's own directory up to the repository root so the
+        // deepest .gitignore gets the first say.
+        for dir in relative.ancestors().skip(1) {
+            match self.dir_gitignore(dir).map(|g| g.matched(&candidate, is_dir)) {
+                Some(Match::Ignore(glob)) => return Some(GitignoreRule::new(glob)),
+                Some(Match::Whitelist(_)) => return None,
+                _ => {}
+            }
+        }
+
+        let fallbacks = self.parents.iter()
+            .chain(self.exclude.iter())
+            .chain(self.global.iter());
+        for gitignore in fallbacks {
+            match gitignore.matched(&candidate, is_dir) {
+                Match::Ignore(glob) => return Some(GitignoreRule::new(glob)),
+                Match::Whitelist(_) => return None,
+                Match::None => {}
+            }
+        }
+
+        None
+    }
+
+    fn dir_gitignore(&mut self, relative_dir: &Path) -> Option<&Gitignore> {
+        if !self.dirs.contains_key(relative_dir) {
+            let dir = self.canonical_root.join(relative_dir);
+            let gitignore = load_gitignore(&dir, &dir.join(".gitignore"));
+            self.dirs.insert(relative_dir.to_path_buf(), gitignore);
+        }
+        self.dirs.get(relative_dir).and_then(Option::as_ref)
+    }
+}
+
+/// The gitignore line that decided to ignore a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitignoreRule {
+    pub file: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub pattern: String,
+}
+
+impl GitignoreRule {
+    fn new(glob: &Glob) -> Self {
+        let file = glob.from().map(Path::to_path_buf);
+        // The ignore crate keeps the pattern but not its line; the last line
+        // with the pattern is the one that matched
+        let line = file.as_ref()
+            .and_then(|file| fs::read_to_string(file).ok())
+            .and_then(|contents| contents.lines().enumerate().filter(|(_, line)| line.trim_end() == glob.original()).last().map(|(index, _)| index + 1));
+        GitignoreRule { file, line, pattern: glob.original().to_string() }
+    }
+}
+
+impl fmt::Display for GitignoreRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{}: {}", file.display(), line, self.pattern),
+            (Some(file), None) => write!(f, "{}: {}", file.display(), self.pattern),
+            (None, _) => write!(f, "gitignore pattern {}", self.pattern),
+        }
+    }
+}
+
+fn find_git_root(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+fn load_gitignore(root: &Path, file: &Path) -> Option<Gitignore> {
+    if !file.is_file() {
+        return None;
+    }
+
+    let mut builder = GitignoreBuilder::new(root);
+    if let Some(e) = builder.add(file) {
+        warning!("Failed to parse {}: {}", file.display(), e);
+    }
+    builder.build()
+        .ok()
+        .filter(|gitignore| !gitignore.is_empty())
+}
@@ -0,0 +1,166 @@
+// language.rs
+use std::path::Path;
+
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("rs", "rust"),
+    ("py", "python"), ("pyi", "python"),
+    ("js", "javascript"), ("mjs", "javascript"), ("cjs", "javascript"), ("jsx", "jsx"),
+    ("ts", "typescript"), ("mts", "typescript"), ("cts", "typescript"), ("tsx", "tsx"),
+    ("go", "go"),
+    ("java", "java"),
+    ("kt", "kotlin"), ("kts", "kotlin"),
+    ("scala", "scala"),
+    ("c", "c"), ("h", "c"),
+    ("cc", "cpp"), ("cpp", "cpp"), ("cxx", "cpp"), ("hh", "cpp"), ("hpp", "cpp"), ("hxx", "cpp"),
+    ("cs", "csharp"),
+    ("fs", "fsharp"),
+    ("swift", "swift"),
+    ("m", "objectivec"), ("mm", "objectivec"),
+    ("rb", "ruby"),
+    ("php", "php"),
+    ("pl", "perl"), ("pm", "perl"),
+    ("lua", "lua"),
+    ("r", "r"),
+    ("dart", "dart"),
+    ("ex", "elixir"), ("exs", "elixir"),
+    ("erl", "erlang"), ("hrl", "erlang"),
+    ("hs", "haskell"),
+    ("ml", "ocaml"), ("mli", "ocaml"),
+    ("clj", "clojure"), ("cljs", "clojure"),
+    ("zig", "zig"),
+    ("nim", "nim"),
+    ("jl", "julia"),
+    ("sh", "bash"), ("bash", "bash"), ("zsh", "zsh"), ("fish", "fish"),
+    ("ps1", "powershell"), ("psm1", "powershell"),
+    ("bat", "batch"), ("cmd", "batch"),
+    ("sql", "sql"),
+    ("html", "html"), ("htm", "html"),
+    ("css", "css"), ("scss", "scss"), ("sass", "sass"), ("less", "less"),
+    ("vue", "vue"), ("svelte", "svelte"),
+    ("json", "json"), ("jsonc", "json"),
+    ("yaml", "yaml"), ("yml", "yaml"),
+    ("toml", "toml"),
+    ("xml", "xml"), ("svg", "xml"),
+    ("ini", "ini"), ("cfg", "ini"),
+    ("md", "markdown"), ("markdown", "markdown"),
+    ("rst", "rst"),
+    ("tex", "latex"),
+    ("proto", "protobuf"),
+    ("graphql", "graphql"), ("gql", "graphql"),
+    ("tf", "hcl"), ("hcl", "hcl"),
+    ("cmake", "cmake"),
+    ("gradle", "groovy"), ("groovy", "groovy"),
+    ("diff", "diff"), ("patch", "diff"),
+];
+
+const FILE_NAMES: &[(&str, &str)] = &[
+    ("Dockerfile", "dockerfile"),
+    ("Containerfile", "dockerfile"),
+    ("Makefile", "makefile"),
+    ("GNUmakefile", "makefile"),
+    ("CMakeLists.txt", "cmake"),
+    ("Gemfile", "ruby"),
+    ("Rakefile", "ruby"),
+    ("Jenkinsfile", "groovy"),
+];
+
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("python", "python"),
+    ("bash", "bash"),
+    ("sh", "bash"),
+    ("zsh", "zsh"),
+    ("fish", "fish"),
+    ("node", "javascript"),
+    ("deno", "typescript"),
+    ("ruby", "ruby"),
+    ("perl", "perl"),
+    ("php", "php"),
+    ("lua", "lua"),
+];
+
+/// Detects a file's language from its name or extension, falling back to the
+/// interpreter named in a `#!` line. Names match common Markdown fence tags.
+pub fn detect_language(path: &Path, content: &str) -> Option<&'static str> {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    if let Some((_, language)) = FILE_NAMES.iter().find(|(name, _)| *name == file_name) {
+        return Some(language);
+    }
+
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        let ext = ext.to_lowercase();
+        if let Some((_, language)) = EXTENSIONS.iter().find(|(e, _)| *e == ext) {
+            return Some(language);
+        }
+    }
+
+    shebang_language(content)
+}
+
+fn shebang_language(content: &str) -> Option<&'static str> {
+    let line = content.lines().next()?.strip_prefix("#!")?;
+    let mut words = line.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|w| !w.starts_with('-'))?;
+    }
+
+    // Strip version suffixes such as python3 or python3.12
+    let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    INTERPRETERS.iter()
+        .find(|(name, _)| *name == program)
+        .map(|(_, language)| *language)
+}
+
+/// Keywords that start a declaration, for outlines and `-f path#symbol`.
+const DECLARATION_KEYWORDS: &[&str] = &[
+    "pub ", "pub(", "fn ", "async fn ", "struct ", "enum ", "trait ", "impl ", "impl<", "mod ", "type ", "const ", "static ", "macro_rules!",
+    "def ", "async def ", "class ", "function ", "async function ", "export ", "interface ",
+    "func ", "package ", "public ", "protected ", "private ", "internal ", "abstract ", "final ", "namespace ",
+    "module ", "object ",
+];
+
+/// Whether a line, without its indentation, starts a declaration.
+pub fn is_declaration(line: &str) -> bool {
+    DECLARATION_KEYWORDS.iter().any(|keyword| line.starts_with(keyword))
+}
+
+/// How comments are written in a language, for counting comment lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommentSyntax {
+    /// Prefixes that comment out the rest of a line.
+    pub line: &'static [&'static str],
+    /// Start and end markers of block comments.
+    pub block: Option<(&'static str, &'static str)>,
+}
+
+const C_STYLE: CommentSyntax = CommentSyntax { line: &["//"], block: Some(("/*", "*/")) };
+const HASH: CommentSyntax = CommentSyntax { line: &["#"], block: None };
+const MARKUP: CommentSyntax = CommentSyntax { line: &[], block: Some(("<!--", "-->")) };
+
+/// Returns the comment syntax of a language named by `detect_language`.
+/// Languages without comments, or unknown ones, have an empty syntax.
+pub fn comment_syntax(language: &str) -> CommentSyntax {
+    match language {
+        "rust" | "javascript" | "jsx" | "typescript" | "tsx" | "go" | "java" | "kotlin" | "scala"
+        | "c" | "cpp" | "csharp" | "swift" | "objectivec" | "dart" | "groovy" | "protobuf"
+        | "scss" | "less" => C_STYLE,
+        "php" => CommentSyntax { line: &["//", "#"], block: Some(("/*", "*/")) },
+        "hcl" => CommentSyntax { line: &["#", "//"], block: Some(("/*", "*/")) },
+        "css" => CommentSyntax { line: &[], block: Some(("/*", "*/")) },
+        "zig" | "sass" => CommentSyntax { line: &["//"], block: None },
+        "fsharp" => CommentSyntax { line: &["//"], block: Some(("(*", "*)")) },
+        "python" | "ruby" | "perl" | "bash" | "zsh" | "fish" | "r" | "yaml" | "toml" | "elixir"
+        | "nim" | "julia" | "makefile" | "dockerfile" | "cmake" | "graphql" => HASH,
+        "powershell" => CommentSyntax { line: &["#"], block: Some(("<#", "#>")) },
+        "ini" => CommentSyntax { line: &[";", "#"], block: None },
+        "lua" => CommentSyntax { line: &["--"], block: Some(("--[[", "]]")) },
+        "sql" => CommentSyntax { line: &["--"], block: Some(("/*", "*/")) },
+        "haskell" => CommentSyntax { line: &["--"], block: Some(("{-", "-}")) },
+        "ocaml" => CommentSyntax { line: &[], block: Some(("(*", "*)")) },
+        "erlang" | "latex" => CommentSyntax { line: &["%"], block: None },
+        "clojure" => CommentSyntax { line: &[";"], block: None },
+        "batch" => CommentSyntax { line: &["::", "REM ", "rem "], block: None },
+        "html" | "xml" | "markdown" | "vue" | "svelte" => MARKUP,
+        _ => CommentSyntax { line: &[], block: None },
+    }
+}
@@ -1,9 +1,37 @@
 mod args;
+mod binary;
+mod budget;
 mod config;
+mod document;
+mod dry_run;
+mod encoding;
+mod errors;
+mod explain;
+mod gitignore;
+mod language;
+mod logging;
+mod selection;
+mod split;
+mod stats;
+mod tokens;
 mod utils;
 mod write;
 
 pub use args::*;
+pub use binary::*;
+pub use budget::*;
 pub use config::*;
+pub use document::*;
+pub use dry_run::*;
+pub use encoding::*;
+pub use errors::*;
+pub use explain::*;
+pub use gitignore::*;
+pub use language::*;
+pub use logging::*;
+pub use selection::*;
+pub use split::*;
+pub use stats::*;
+pub use tokens::*;
 pub use utils::*;
 pub use write::*;
\ No newline at end of file
@@ -0,0 +1,216 @@
+// logging.rs
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::args::Args;
+use crate::binary::format_size;
+use crate::tokens::format_count;
+
+/// How much is reported on stderr. Messages are shown when their level is at
+/// or below the selected one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Warnings and errors; always shown.
+    Warn,
+    /// The run summary and progress; hidden by `-q`.
+    Info,
+    /// Details of each step, shown with `-v`.
+    Verbose,
+    /// Internal state, shown with `-vv`.
+    Debug,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static PROGRESS: Mutex<Option<Progress>> = Mutex::new(None);
+
+/// Sets the level from `-q` and the number of `-v` flags.
+pub fn init(args: &Args) {
+    let level = match (args.quiet, args.verbose) {
+        (true, _) => Level::Warn,
+        (false, 0) => Level::Info,
+        (false, 1) => Level::Verbose,
+        (false, _) => Level::Debug,
+    };
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// Prints a message on stderr if its level is enabled, clearing the progress
+/// line first so the two never mix.
+pub fn log(level: Level, message: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    let mut progress = PROGRESS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(progress) = progress.as_mut() {
+        progress.clear();
+    }
+    match level {
+        Level::Warn => eprintln!("Warning: {}", message),
+        Level::Debug => eprintln!("Debug: {}", message),
+        Level::Info | Level::Verbose => eprintln!("{}", message),
+    }
+}
+
+// The library and the binary each use only some of these
+#[allow(unused_macros)]
+macro_rules! warning {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Level::Warn, format_args!($($arg)*)) };
+}
+
+#[allow(unused_macros)]
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Level::Info, format_args!($($arg)*)) };
+}
+
+#[allow(unused_macros)]
+macro_rules! verbose {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Level::Verbose, format_args!($($arg)*)) };
+}
+
+#[allow(unused_macros)]
+macro_rules! debug {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Level::Debug, format_args!($($arg)*)) };
+}
+
+#[allow(unused_imports)]
+pub(crate) use {debug, info, verbose, warning};
+
+/// Progress is only drawn for runs that take longer than this.
+const PROGRESS_DELAY: Duration = Duration::from_millis(500);
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A single self-updating status line on stderr, drawn only when stderr is a
+/// terminal.
+struct Progress {
+    start: Instant,
+    last_drawn: Option<Instant>,
+    scanned: usize,
+    writing: Option<Writing>,
+}
+
+/// Totals of the files about to be written, to estimate the time left.
+struct Writing {
+    start: Instant,
+    total_files: usize,
+    total_bytes: u64,
+    files: usize,
+    bytes: u64,
+    tokens: usize,
+    file_tokens: HashMap<PathBuf, usize>,
+}
+
+impl Progress {
+    fn line(&self) -> String {
+        let Some(writing) = &self.writing else {
+            return format!("Scanning: {} entries", format_count(self.scanned));
+        };
+
+        let mut line = format!(
+            "Writing: {}/{} files, {}",
+            format_count(writing.files), format_count(writing.total_files), format_size(writing.bytes),
+        );
+        if !writing.file_tokens.is_empty() {
+            line.push_str(&format!(", {} tokens", format_count(writing.tokens)));
+        }
+        if writing.bytes > 0 && writing.bytes < writing.total_bytes {
+            let elapsed = writing.start.elapsed().as_secs_f64();
+            let remaining = elapsed * (writing.total_bytes - writing.bytes) as f64 / writing.bytes as f64;
+            line.push_str(&format!(", ETA {}s", remaining.ceil() as u64));
+        }
+        line
+    }
+
+    fn draw(&mut self, force: bool) {
+        let now = Instant::now();
+        if now.duration_since(self.start) < PROGRESS_DELAY {
+            return;
+        }
+        if !force && self.last_drawn.is_some_and(|last| now.duration_since(last) < PROGRESS_INTERVAL) {
+            return;
+        }
+        self.last_drawn = Some(now);
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K{}", self.line());
+        let _ = stderr.flush();
+    }
+
+    fn clear(&mut self) {
+        if self.last_drawn.take().is_some() {
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "\r\x1b[2K");
+            let _ = stderr.flush();
+        }
+    }
+}
+
+fn with_progress(update: impl FnOnce(&mut Progress)) {
+    let mut progress = PROGRESS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(progress) = progress.as_mut() {
+        update(progress);
+    }
+}
+
+/// Starts the progress line, unless progress is hidden or stderr is not a terminal.
+pub fn start_progress() {
+    if enabled(Level::Info) && io::stderr().is_terminal() {
+        *PROGRESS.lock().unwrap_or_else(|e| e.into_inner()) = Some(Progress {
+            start: Instant::now(),
+            last_drawn: None,
+            scanned: 0,
+            writing: None,
+        });
+    }
+}
+
+/// Counts an entry found while walking the repository.
+pub fn entry_scanned() {
+    with_progress(|progress| {
+        progress.scanned += 1;
+        progress.draw(false);
+    });
+}
+
+/// Switches the progress line to writing `files`. With token counts, the
+/// tokens written so far are shown as well.
+pub fn start_writing<'a>(files: impl IntoIterator<Item = &'a Path>, file_tokens: impl FnOnce() -> HashMap<PathBuf, usize>) {
+    with_progress(|progress| {
+        let sizes: Vec<u64> = files.into_iter().map(|path| path.metadata().map(|m| m.len()).unwrap_or(0)).collect();
+        progress.writing = Some(Writing {
+            start: Instant::now(),
+            total_files: sizes.len(),
+            total_bytes: sizes.iter().sum(),
+            files: 0,
+            bytes: 0,
+            tokens: 0,
+            file_tokens: file_tokens(),
+        });
+        progress.draw(true);
+    });
+}
+
+/// Counts a file whose contents have been written.
+pub fn file_written(path: &Path) {
+    with_progress(|progress| {
+        if let Some(writing) = progress.writing.as_mut() {
+            writing.files += 1;
+            writing.bytes += path.metadata().map(|m| m.len()).unwrap_or(0);
+            writing.tokens += writing.file_tokens.get(path).copied().unwrap_or(0);
+        }
+        progress.draw(false);
+    });
+}
+
+/// Removes the progress line for good.
+pub fn finish_progress() {
+    if let Some(mut progress) = PROGRESS.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        progress.clear();
+    }
+}
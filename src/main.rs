@@ -1,17 +1,47 @@
 use std::fs::File;
-use std::io::{self, Write};
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
-use globset::{Glob, GlobSet, GlobSetBuilder};
-use std::sync::Arc;
+use walkdir::DirEntry;
 
 mod args;
+mod binary;
+mod budget;
 mod config;
+mod document;
+mod dry_run;
+mod encoding;
+mod errors;
+mod explain;
+mod gitignore;
+mod language;
+mod logging;
+mod selection;
+mod split;
+mod stats;
+mod tokens;
 mod utils;
 mod write;
 use crate::args::{Args, OutputFormat, parse_args};
+use crate::binary::detect_binary;
+use crate::budget::{BudgetPlan, BudgetPlanner};
 use crate::config::Config;
-use crate::utils::walk_entries;
-use crate::write::{write_tree, write_file_content, write_file_contents};
+use crate::document::{build_document, build_selection_document, build_tree};
+use crate::dry_run::write_dry_run;
+use crate::encoding::read_text_file;
+use crate::errors::{SkipLog, EXIT_SKIPPED};
+use crate::explain::explain_paths;
+use crate::logging::{debug, info, verbose, warning};
+use crate::selection::{resolve_selectors, SelectedFile};
+use crate::split::{part_path, PartSizer};
+use crate::stats::{RepoStats, StatsCollector};
+use crate::tokens::{format_count, TokenCounter, TokenCounts};
+use crate::utils::{relative_path_string, resolve_include_dirs, walk_entries, HiddenAllowlist, IgnoreGlobs};
+use crate::write::{
+    write_header, write_footer, write_tree_section, write_stats_section, write_contents_section, write_contents_start, write_contents_end,
+    write_file_block, write_file_content, write_selected_files, write_parts_index, write_skipped, write_budget_trailer, write_json_document,
+    FileBody,
+};
 
 fn load_default_config() -> Config {
     let json_str = include_str!("config.json");
@@ -25,91 +55,374 @@ fn load_config_from_file(file_path: &Path) -> io::Result<Config> {
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Failed to parse config file: {}", e)))
 }
 
-fn create_globset(args: &Args) -> io::Result<Arc<GlobSet>> {
-    let mut glob_builder = GlobSetBuilder::new();
-    
-    // Process ignore_files as exact glob patterns
-    for pattern in &args.ignore_files {
-        glob_builder.add(Glob::new(pattern).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid ignore file pattern '{}': {}", pattern, e)))?);
-    }
-    
-    // Process ignore_types by trimming leading dots and creating *.ext globs
-    for pattern in &args.ignore_types {
-        let trimmed = pattern.trim_start_matches('.');
-        let glob_pattern = format!("*.{}", trimmed);
-        glob_builder.add(Glob::new(&glob_pattern).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid ignore type pattern '{}': {}", pattern, e)))?);
-    }
-    
-    // Build the glob set
-    glob_builder.build()
-        .map(Arc::new)
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("Failed to build GlobSet: {}", e)))
-}
-
-fn write_header(output_file: &mut File, args: &Args) -> io::Result<()> {
-    match args.output_format {
-        OutputFormat::Text => {
-            writeln!(output_file, "Repository Documentation")?;
-            writeln!(output_file, "This document provides an overview of the repository's structure and contents.")?;
-            writeln!(output_file, "The 'Directory/File Tree' section displays the repository's hierarchy.")?;
-            writeln!(output_file, "The 'File Content' section details the contents of each file.")?;
-            writeln!(output_file, "File contents are marked with '[File Begins]' and '[File Ends]' tags.\n")?;
-        },
-        OutputFormat::Markdown => {
-            writeln!(output_file, "# Repository Documentation")?;
-            writeln!(output_file, "This document provides an overview of the repository's structure and contents.\n")?;
-            writeln!(output_file, "## Directory/File Tree")?;
-            writeln!(output_file, "The following section displays the repository's hierarchy.\n")?;
-            writeln!(output_file, "## File Content")?;
-            writeln!(output_file, "This section details the contents of each file.\n")?;
+/// Number of files listed in the token summary.
+const TOKEN_SUMMARY_FILES: usize = 10;
+
+fn print_token_summary(counter: &TokenCounter, token_counts: &TokenCounts, outputs: &[Written], repo_path: &Path) {
+    if let Some(total) = count_output_tokens(outputs) {
+        info!("Total tokens ({}): {}", counter.name(), format_count(total));
+    }
+
+    let largest = token_counts.largest_files();
+    if largest.is_empty() {
+        return;
+    }
+    info!("Tokens per file (largest first):");
+    for (path, tokens) in largest.iter().take(TOKEN_SUMMARY_FILES) {
+        let path = path.strip_prefix(repo_path).unwrap_or(path);
+        info!("  {:>10}  {}", format_count(*tokens), path.display());
+    }
+    if largest.len() > TOKEN_SUMMARY_FILES {
+        info!("  ... and {} more files", largest.len() - TOKEN_SUMMARY_FILES);
+    }
+}
+
+/// Tokens of everything written, across all parts, if they were counted.
+fn count_output_tokens(outputs: &[Written]) -> Option<usize> {
+    outputs.iter()
+        .map(|output| match output {
+            Written::File(_, tokens) | Written::Stdout(tokens) => *tokens,
+        })
+        .sum()
+}
+
+fn print_budget_summary(plan: &BudgetPlan, outputs: &[Written]) {
+    let reductions = plan.reductions();
+    let count = |label: &str| reductions.iter().filter(|r| r.fit.label() == label).count();
+    info!(
+        "Token budget of {}: {} file(s) truncated, {} outlined, {} dropped",
+        format_count(plan.max_tokens()), count("truncated"), count("outlined"), count("dropped"),
+    );
+
+    if let Some(total) = count_output_tokens(outputs) {
+        if total > plan.max_tokens() {
+            warning!("The document has {} tokens, over the budget of {}", format_count(total), format_count(plan.max_tokens()));
+        }
+    }
+}
+
+/// Canonical path of the output file, which may not exist yet.
+fn canonical_output_path(output_file: &str) -> PathBuf {
+    let path = Path::new(output_file);
+    if let Ok(canonical) = std::fs::canonicalize(path) {
+        return canonical;
+    }
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    match (std::fs::canonicalize(dir), path.file_name()) {
+        (Ok(dir), Some(name)) => dir.join(name),
+        _ => PathBuf::from(output_file),
+    }
+}
+
+fn create_output_file(path: &Path) -> io::Result<BufWriter<File>> {
+    File::create(path)
+        .map(BufWriter::new)
+        .map_err(|e| io::Error::other(format!("Failed to create output file '{}': {}", path.display(), e)))
+}
+
+/// A finished document, with its tokens if they were counted.
+enum Written {
+    File(PathBuf, Option<usize>),
+    Stdout(Option<usize>),
+}
+
+/// Passes everything through to `inner`, counting the tokens written if a
+/// counter is given.
+///
+/// Text is counted a line at a time, so only the current line is held in
+/// memory. A line is only counted once the next write starts with a
+/// printable character other than `/`: the tokenizers never join such a
+/// character with a preceding newline, so the total matches counting the
+/// whole output at once.
+struct CountingWriter<'a, W: Write> {
+    inner: W,
+    counter: Option<&'a TokenCounter>,
+    pending: Vec<u8>,
+    tokens: usize,
+}
+
+impl<'a, W: Write> CountingWriter<'a, W> {
+    fn new(inner: W, counter: Option<&'a TokenCounter>) -> Self {
+        CountingWriter { inner, counter, pending: Vec::new(), tokens: 0 }
+    }
+
+    /// Tokens written so far, or `None` without a counter.
+    fn tokens(&self) -> Option<usize> {
+        self.counter.map(|counter| self.tokens + counter.count(&String::from_utf8_lossy(&self.pending)))
+    }
+}
+
+impl<W: Write> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(counter) = self.counter {
+            let buf = &buf[..written];
+            if self.pending.ends_with(b"\n") && buf.first().is_some_and(|&b| b.is_ascii_graphic() && b != b'/') {
+                self.tokens += counter.count(&String::from_utf8_lossy(&self.pending));
+                self.pending.clear();
+            }
+            self.pending.extend_from_slice(buf);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads each walked file once for the token counts, the statistics and the
+/// budget plan, whichever are wanted. Nothing is read if none of them is.
+fn scan_entries(entries: &[DirEntry], args: &Args, counter: Option<&TokenCounter>, token_counts: &mut TokenCounts, mut stats: Option<&mut StatsCollector>, mut planner: Option<&mut BudgetPlanner>) {
+    let count_files = args.count_tokens || args.tree_tokens;
+    if !count_files && stats.is_none() && planner.is_none() {
+        return;
+    }
+    let root = entries.first().map(|e| e.path()).unwrap_or(Path::new(""));
+
+    for entry in entries.iter().filter(|e| e.file_type().is_file()) {
+        let path = entry.path();
+        // Unreadable files are left for the writers to report
+        let body = detect_binary(path)
+            .and_then(|binary| match binary {
+                Some(info) => Ok(FileBody::Binary(info)),
+                None => read_text_file(path).map(|decoded| FileBody::Text(decoded.text)),
+            })
+            .ok();
+        let tokens = match (&body, counter) {
+            (Some(FileBody::Text(text)), Some(counter)) => Some(counter.count(text)),
+            _ => None,
+        };
+
+        if let (true, Some(tokens)) = (count_files, tokens) {
+            token_counts.add(path, root, tokens);
+        }
+        if let Some(stats) = stats.as_deref_mut() {
+            stats.add(path, relative_path_string(path, &args.repo_path), body.as_ref());
+        }
+        if let Some(planner) = planner.as_deref_mut() {
+            planner.add(path, body.as_ref(), tokens);
+        }
+    }
+}
+
+/// What a run documents.
+enum Source<'a> {
+    /// The walked entries of the repository.
+    Repository(&'a [DirEntry]),
+    /// The files and lines selected with `--file-path`.
+    Files(&'a [SelectedFile]),
+}
+
+/// Writes the whole document to the output file, or streams it to stdout.
+fn write_output(source: Source, args: &Args, tree_tokens: Option<&TokenCounts>, stats: Option<&RepoStats>, budget: Option<&BudgetPlan>, counter: Option<&TokenCounter>, skipped: &mut SkipLog) -> io::Result<Written> {
+    if args.writes_to_stdout() {
+        let mut stdout = CountingWriter::new(BufWriter::new(io::stdout().lock()), counter);
+        write_document(&mut stdout, source, args, tree_tokens, stats, budget, skipped)?;
+        stdout.flush()?;
+        return Ok(Written::Stdout(stdout.tokens()));
+    }
+
+    let output_path = PathBuf::from(&args.output_file);
+    let mut output_file = CountingWriter::new(create_output_file(&output_path)?, counter);
+    debug!("output file created: {}", args.output_file);
+    write_document(&mut output_file, source, args, tree_tokens, stats, budget, skipped)?;
+    output_file.flush()?;
+    Ok(Written::File(output_path, output_file.tokens()))
+}
+
+fn write_document<W: Write>(output_file: &mut W, source: Source, args: &Args, tree_tokens: Option<&TokenCounts>, stats: Option<&RepoStats>, budget: Option<&BudgetPlan>, skipped: &mut SkipLog) -> io::Result<()> {
+    // Write documentation header
+    write_header(output_file, args, None)?;
+
+    // Tree, statistics and file documents written so far, used to number XML documents
+    let mut documents_written = 0;
+
+    match source {
+        Source::Repository(entries) => {
+            if args.output_format == OutputFormat::Json {
+                let mut document = build_document(entries, args, budget, skipped);
+                document.statistics = stats.cloned();
+                write_json_document(&document, output_file)?;
+            } else {
+                // Write directory tree
+                write_tree_section(entries, output_file, args, tree_tokens)?;
+                documents_written += 1;
+
+                if let Some(stats) = stats {
+                    write_stats_section(stats, output_file, args, documents_written + 1)?;
+                    documents_written += 1;
+                }
+
+                // Write file contents
+                documents_written += write_contents_section(entries, output_file, args, budget, documents_written + 1, skipped)?;
+            }
         },
-        OutputFormat::HTML => {
-            writeln!(output_file, "<!DOCTYPE html>")?;
-            writeln!(output_file, "<html lang=\"en\">")?;
-            writeln!(output_file, "<head>")?;
-            writeln!(output_file, "    <meta charset=\"UTF-8\">")?;
-            writeln!(output_file, "    <meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">")?;
-            writeln!(output_file, "    <title>Repository Documentation</title>")?;
-            writeln!(output_file, "</head>")?;
-            writeln!(output_file, "<body>")?;
-            writeln!(output_file, "    <h1>Repository Documentation</h1>")?;
-            writeln!(output_file, "    <p>This document provides an overview of the repository's structure and contents.</p>")?;
-            writeln!(output_file, "    <h2>Directory/File Tree</h2>")?;
-            writeln!(output_file, "    <p>The following section displays the repository's hierarchy.</p>")?;
-            writeln!(output_file, "    <h2>File Content</h2>")?;
-            writeln!(output_file, "    <p>This section details the contents of each file.</p>")?;
+        Source::Files(files) => {
+            if args.output_format == OutputFormat::Json {
+                write_json_document(&build_selection_document(files, args, skipped), output_file)?;
+            } else if let [SelectedFile { path, ranges: None }] = files {
+                // A single whole file is written as it is, without markers
+                write_file_content(path, output_file, args, skipped)?;
+                documents_written += 1;
+            } else {
+                documents_written += write_selected_files(files, output_file, args, skipped)?;
+            }
         },
     }
+
+    // JSON documents carry their own budget record and list of skipped paths
+    if args.output_format != OutputFormat::Json {
+        write_trailers(output_file, args, budget, skipped, documents_written + 1)?;
+    }
+    write_footer(output_file, args)
+}
+
+/// Writes the budget trailer and the skipped paths, if there is anything to
+/// list. `document_index` is the first unused XML document index.
+fn write_trailers<W: Write>(output_file: &mut W, args: &Args, budget: Option<&BudgetPlan>, skipped: &SkipLog, mut document_index: usize) -> io::Result<()> {
+    if let Some(plan) = budget.filter(|plan| !plan.reductions().is_empty()) {
+        write_budget_trailer(plan, output_file, args, document_index)?;
+        document_index += 1;
+    }
+    if !skipped.is_empty() {
+        write_skipped(skipped, output_file, args, document_index)?;
+    }
     Ok(())
 }
 
+/// Writes what comes before the files of a part: the header and, in part 1,
+/// the tree, the index of parts and the statistics.
+fn write_part_start<W: Write>(output_file: &mut W, entries: &[DirEntry], args: &Args, part: (usize, usize), tree_tokens: Option<&TokenCounts>, index: &[(String, Vec<String>)], stats: Option<&RepoStats>) -> io::Result<()> {
+    write_header(output_file, args, Some(part))?;
+    if part.0 == 1 {
+        write_tree_section(entries, output_file, args, tree_tokens)?;
+        write_parts_index(index, output_file, args, 2)?;
+        if let Some(stats) = stats {
+            write_stats_section(stats, output_file, args, 3)?;
+        }
+    }
+    write_contents_start(output_file, args)
+}
+
+/// Writes what comes after the files of a part: the closing markers and, in
+/// the last part, the trailers, if `trailers` is given.
+fn write_part_end<W: Write>(output_file: &mut W, args: &Args, trailers: Option<(Option<&BudgetPlan>, &SkipLog, usize)>) -> io::Result<()> {
+    write_contents_end(output_file, args)?;
+    if let Some((budget, skipped, document_index)) = trailers {
+        write_trailers(output_file, args, budget, skipped, document_index)?;
+    }
+    write_footer(output_file, args)
+}
+
+/// Writes the document as numbered parts that stay under the size limit,
+/// never splitting a file. Part 1 holds the tree and an index of the parts;
+/// the last part holds the trailers.
+#[allow(clippy::too_many_arguments)]
+fn write_split_document(entries: &[DirEntry], args: &Args, sizer: &PartSizer, tree_tokens: Option<&TokenCounts>, stats: Option<&RepoStats>, budget: Option<&BudgetPlan>, counter: Option<&TokenCounter>, skipped: &mut SkipLog) -> io::Result<Vec<Written>> {
+    // Render every file first; the number of parts must be known before the headers are written.
+    // In XML, document 1 is the tree, document 2 the index of parts and document 3 the statistics.
+    let first_file_index = if stats.is_some() { 4 } else { 3 };
+    let mut blocks: Vec<(String, Vec<u8>)> = Vec::new();
+    for entry in entries.iter().filter(|e| e.file_type().is_file()) {
+        let mut block = Vec::new();
+        if write_file_block(entry.path(), &mut block, args, budget, first_file_index + blocks.len(), skipped)? {
+            blocks.push((relative_path_string(entry.path(), &args.repo_path), block));
+        }
+    }
+
+    let output_file = Path::new(&args.output_file);
+    let parts_index = |parts: &[Range<usize>]| -> Vec<(String, Vec<String>)> {
+        parts.iter().enumerate()
+            .map(|(number, range)| {
+                let name = part_path(output_file, number + 1).file_name().unwrap_or_default().to_string_lossy().into_owned();
+                (name, blocks[range.clone()].iter().map(|(path, _)| path.clone()).collect())
+            })
+            .collect()
+    };
+
+    // The index and the headers depend on how the files are packed, so the
+    // packing is repeated with what the parts actually take around their files
+    // until it fits. Sizes only grow, so this ends.
+    let block_sizes: Vec<usize> = blocks.iter().map(|(_, block)| sizer.measure(block)).collect();
+    let mut trailers = Vec::new();
+    write_trailers(&mut trailers, args, budget, skipped, first_file_index + blocks.len())?;
+    let trailers = sizer.measure(&trailers);
+    let (mut first_overhead, mut overhead) = (0, 0);
+    let parts = loop {
+        let parts = sizer.pack(&block_sizes, first_overhead, overhead, trailers);
+        let total = parts.len();
+        let mut first = Vec::new();
+        write_part_start(&mut first, entries, args, (1, total), tree_tokens, &parts_index(&parts), stats)?;
+        write_part_end(&mut first, args, None)?;
+        let mut other = Vec::new();
+        write_part_start(&mut other, entries, args, (total, total), tree_tokens, &[], stats)?;
+        write_part_end(&mut other, args, None)?;
+
+        let (first, other) = (sizer.measure(&first), sizer.measure(&other));
+        if first <= first_overhead && other <= overhead {
+            break parts;
+        }
+        first_overhead = first_overhead.max(first);
+        overhead = overhead.max(other);
+    };
+    let index = parts_index(&parts);
+    let part_paths: Vec<PathBuf> = (1..=parts.len()).map(|number| part_path(output_file, number)).collect();
+
+    let mut written = Vec::new();
+    for (number, (path, range)) in part_paths.into_iter().zip(&parts).enumerate() {
+        let mut part_file = CountingWriter::new(create_output_file(&path)?, counter);
+        let part = (number + 1, parts.len());
+        write_part_start(&mut part_file, entries, args, part, tree_tokens, &index, stats)?;
+        for (_, block) in &blocks[range.clone()] {
+            part_file.write_all(block)?;
+        }
+        let last = number + 1 == parts.len();
+        write_part_end(&mut part_file, args, last.then_some((budget, &*skipped, first_file_index + blocks.len())))?;
+        part_file.flush()?;
+        verbose!("Wrote part {} of {} with {} file(s): {}", part.0, part.1, range.len(), path.display());
+        written.push(Written::File(path, part_file.tokens()));
+    }
+    Ok(written)
+}
+
 fn main() -> io::Result<()> {
     // Parse command line arguments
-    let args = parse_args();
-    println!("Debug: args = {:?}", args);
+    let mut args = parse_args();
+    logging::init(&args);
+    debug!("args = {:?}", args);
 
     // Load configuration
     let config = if let Some(config_path) = &args.config_path {
         load_config_from_file(config_path).unwrap_or_else(|e| {
-            eprintln!("Warning: Failed to load config from file: {}. Using default config.\nError: {}", config_path.display(), e);
+            warning!("Failed to load config from file: {}. Using default config.\nError: {}", config_path.display(), e);
             load_default_config()
         })
     } else {
         load_default_config()
     };
-    println!("Debug: config loaded");
+    debug!("config loaded");
 
-    // Create output file
-    let mut output_file = File::create(&args.output_file)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Failed to create output file '{}': {}", args.output_file, e)))?;
-    println!("Debug: output file created: {}", args.output_file);
+    let sizer = PartSizer::from_args(&args);
+    if sizer.is_some() && matches!(args.output_format, OutputFormat::Json | OutputFormat::Jsonl) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "--split-tokens and --split-bytes are not supported with JSON output"));
+    }
+    if sizer.is_some() && args.writes_to_stdout() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "--split-tokens and --split-bytes need an output file to name the parts after"));
+    }
 
-    // Get canonical path for output file
-    let output_file_path = std::fs::canonicalize(&args.output_file)
-        .unwrap_or_else(|_| PathBuf::from(&args.output_file));
+    // Get canonical path for output file; nothing needs excluding when writing to stdout
+    let output_file_path = if args.writes_to_stdout() {
+        PathBuf::new()
+    } else {
+        canonical_output_path(&args.output_file)
+    };
 
-    // Write documentation header
-    write_header(&mut output_file, &args)?;
+    // Resolve include dirs once, so walked paths can be compared with them as they are
+    if !args.include_dir.is_empty() && args.file_path.is_empty() {
+        let repo_path = if args.repo_path.as_os_str().is_empty() { Path::new(".") } else { &args.repo_path };
+        args.include_dir = resolve_include_dirs(repo_path, &args.include_dir)?;
+    }
 
     // Determine repository path
     let repo_path = if args.repo_path.as_os_str().is_empty() {
@@ -117,17 +430,35 @@ fn main() -> io::Result<()> {
     } else {
         &args.repo_path
     };
-    println!("Debug: Using repo_path = {:?}", repo_path);
+    debug!("Using repo_path = {:?}", repo_path);
 
     // Create glob patterns for file filtering
-    let globset = create_globset(&args)?;
+    let globs = IgnoreGlobs::new(&args)?;
+    let hidden_allowlist = HiddenAllowlist::new(args.allow_hidden.iter().chain(&config.hidden_allowlist), args.glob_case_insensitive)?;
 
-    // Handle single file mode vs repository mode
-    if let Some(file_path) = &args.file_path {
-        if !file_path.exists() {
-            return Err(io::Error::new(io::ErrorKind::NotFound, format!("The specified file does not exist: {}", file_path.display())));
+    let token_counter = (args.count_tokens || args.tree_tokens || args.max_tokens.is_some()).then(|| TokenCounter::new(args.tokenizer));
+    let mut token_counts = TokenCounts::default();
+    let mut stats = None;
+    let mut budget = None;
+
+    if let Some(target) = &args.explain {
+        if !repo_path.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("The specified directory does not exist or is not a directory: {}", repo_path.display())));
         }
-        write_file_content(file_path, &mut output_file)?;
+        let mut stdout = BufWriter::new(io::stdout().lock());
+        return match explain_paths(&mut stdout, repo_path, target.as_deref(), &args, &config, &globs, &hidden_allowlist, &output_file_path).and_then(|_| stdout.flush()) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => result,
+        };
+    }
+
+    // Paths that could not be documented are collected instead of aborting the run
+    let mut skipped = SkipLog::new();
+
+    // Handle single file mode vs repository mode
+    let selected = resolve_selectors(&args.file_path)?;
+    let entries = if !selected.is_empty() {
+        None
     } else {
         // Repository mode
         if !repo_path.is_dir() {
@@ -135,23 +466,99 @@ fn main() -> io::Result<()> {
         }
 
         // Get all entries
-        let entries = walk_entries(repo_path, &args, &config, Arc::clone(&globset), &output_file_path);
-
-        // Write directory tree
-        writeln!(output_file, "Directory/File Tree Begins -->\n")?;
-        write_tree(&entries, &mut output_file)?;
-        writeln!(output_file, "\n<-- Directory/File Tree Ends")?;
+        logging::start_progress();
+        Some(walk_entries(repo_path, &args, &config, &globs, &hidden_allowlist, &output_file_path, &mut skipped))
+    };
 
-        // Write file contents
-        writeln!(output_file, "\nFile Content Begins -->\n")?;
-        write_file_contents(&entries, &mut output_file, &args)?;
-        writeln!(output_file, "\n<-- File Content Ends\n")?;
+    if let (true, Some(entries)) = (args.dry_run, &entries) {
+        let counter = TokenCounter::new(args.tokenizer);
+        let mut stdout = BufWriter::new(io::stdout().lock());
+        match write_dry_run(&mut stdout, entries, &args, &counter, &mut skipped).and_then(|_| stdout.flush()) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            result => result?,
+        }
+        skipped.print_summary(repo_path);
+        if args.strict && !skipped.is_empty() {
+            std::process::exit(EXIT_SKIPPED);
+        }
+        return Ok(());
     }
 
-    if args.output_format == OutputFormat::HTML {
-        writeln!(output_file, "</body>\n</html>")?;
+    if let Some(entries) = &entries {
+        verbose!("Found {} files to document", entries.iter().filter(|e| e.file_type().is_file()).count());
+        let mut stats_collector = args.stats.then(StatsCollector::new);
+        let mut planner = match (args.max_tokens, &token_counter) {
+            (Some(_), Some(counter)) => Some(BudgetPlanner::new(&args, &config, counter)?),
+            _ => None,
+        };
+        scan_entries(entries, &args, token_counter.as_ref(), &mut token_counts, stats_collector.as_mut(), planner.as_mut());
+        if let (true, Some(counter)) = (args.count_tokens || args.tree_tokens, &token_counter) {
+            verbose!("Counted tokens with {}", counter.name());
+        }
+        stats = stats_collector.map(StatsCollector::finish);
+
+        // Plan which files fit, after setting aside what the header and tree take
+        if let (Some(max_tokens), Some(planner), Some(counter)) = (args.max_tokens, planner, &token_counter) {
+            let mut preamble = Vec::new();
+            write_header(&mut preamble, &args, None)?;
+            if args.output_format == OutputFormat::Json {
+                serde_json::to_writer_pretty(&mut preamble, &build_tree(entries, &args))?;
+                serde_json::to_writer_pretty(&mut preamble, &stats)?;
+            } else {
+                write_tree_section(entries, &mut preamble, &args, args.tree_tokens.then_some(&token_counts))?;
+                if let Some(stats) = &stats {
+                    write_stats_section(stats, &mut preamble, &args, 2)?;
+                }
+            }
+            let reserved = counter.count(&String::from_utf8_lossy(&preamble));
+            let plan = planner.finish(max_tokens, reserved);
+            verbose!("Planned a budget of {} tokens, {} reserved for the header and tree; {} file(s) shortened or dropped", format_count(max_tokens), format_count(reserved), plan.reductions().len());
+            budget = Some(plan);
+        }
+
+        let files = entries.iter().filter(|e| e.file_type().is_file()).map(|e| e.path());
+        logging::start_writing(files, || {
+            token_counts.largest_files().into_iter().map(|(path, tokens)| (path.to_path_buf(), tokens)).collect()
+        });
     }
+    let tree_tokens = args.tree_tokens.then_some(&token_counts);
+    // The output is counted for the summary and to check the budget
+    let output_counter = token_counter.as_ref().filter(|_| args.count_tokens || args.max_tokens.is_some());
+
+    let result = match (&entries, &sizer) {
+        (Some(entries), Some(sizer)) => write_split_document(entries, &args, sizer, tree_tokens, stats.as_ref(), budget.as_ref(), output_counter, &mut skipped),
+        (Some(entries), None) => write_output(Source::Repository(entries), &args, tree_tokens, stats.as_ref(), budget.as_ref(), output_counter, &mut skipped).map(|written| vec![written]),
+        (None, _) => write_output(Source::Files(&selected), &args, tree_tokens, stats.as_ref(), budget.as_ref(), output_counter, &mut skipped).map(|written| vec![written]),
+    };
+    logging::finish_progress();
+    let outputs = match result {
+        Ok(outputs) => outputs,
+        // The reader of a pipeline such as `repo2txt | head` stopped early; that is not an error
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe && args.writes_to_stdout() => return Ok(()),
+        Err(e) => return Err(e),
+    };
 
-    println!("Documentation generated successfully. Output written to: {}", args.output_file);
+    match outputs.as_slice() {
+        [Written::Stdout(_)] => info!("Documentation generated successfully. Output written to stdout"),
+        [Written::File(path, _)] if sizer.is_none() => info!("Documentation generated successfully. Output written to: {}", path.display()),
+        _ => {
+            info!("Documentation generated successfully. Output written to {} parts:", outputs.len());
+            for output in &outputs {
+                if let Written::File(path, _) = output {
+                    info!("  {}", path.display());
+                }
+            }
+        }
+    }
+    if let (true, Some(counter)) = (args.count_tokens, &token_counter) {
+        print_token_summary(counter, &token_counts, &outputs, repo_path);
+    }
+    if let Some(plan) = &budget {
+        print_budget_summary(plan, &outputs);
+    }
+    skipped.print_summary(repo_path);
+    if args.strict && !skipped.is_empty() {
+        std::process::exit(EXIT_SKIPPED);
+    }
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,365 @@
+// selection.rs
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::binary::detect_binary;
+use crate::encoding::read_text_file;
+use crate::language::{comment_syntax, detect_language, is_declaration};
+use crate::write::FileBody;
+
+/// Words that may come before the name in a declaration.
+const DECLARATION_MODIFIERS: &[&str] = &[
+    "pub", "crate", "super", "self", "in", "async", "unsafe", "extern", "fn", "struct", "enum", "trait", "impl", "mod",
+    "type", "const", "static", "macro_rules", "def", "class", "function", "export", "default", "interface", "func",
+    "package", "public", "protected", "private", "internal", "abstract", "final", "namespace", "module", "object",
+    "let", "var", "val", "fun", "override", "virtual", "sealed", "data", "open", "inline",
+];
+
+/// A `--file-path` argument: a file, optionally narrowed to a range of lines
+/// (`path:START-END`) or to the declaration of a symbol (`path#symbol`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSelector {
+    pub path: PathBuf,
+    pub selection: Selection,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selection {
+    Whole,
+    /// 1-based and inclusive; without an end, up to the end of the file.
+    Lines { start: usize, end: Option<usize> },
+    /// A function, type or other declaration, or a Markdown heading. Nested
+    /// symbols are written `Outer::inner` or `Outer.inner`.
+    Symbol(String),
+}
+
+impl FromStr for FileSelector {
+    type Err = String;
+
+    /// Paths that exist are taken as they are, so file names containing `:`
+    /// or `#` need no escaping.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let whole = FileSelector { path: PathBuf::from(s), selection: Selection::Whole };
+        if s.is_empty() || Path::new(s).exists() {
+            return Ok(whole);
+        }
+        if let Some((path, symbol)) = s.rsplit_once('#').filter(|(path, symbol)| !path.is_empty() && !symbol.is_empty()) {
+            return Ok(FileSelector { path: PathBuf::from(path), selection: Selection::Symbol(symbol.to_string()) });
+        }
+        if let Some((path, range)) = s.rsplit_once(':').filter(|(path, range)| !path.is_empty() && range.starts_with(|c: char| c.is_ascii_digit())) {
+            return Ok(FileSelector { path: PathBuf::from(path), selection: parse_line_range(range)? });
+        }
+        Ok(whole)
+    }
+}
+
+fn parse_line_range(range: &str) -> Result<Selection, String> {
+    let invalid = || format!("Invalid line range '{}': expected START-END, START- or LINE", range);
+    let (start, end) = match range.split_once('-') {
+        Some((start, "")) => (start, None),
+        Some((start, end)) => (start, Some(end.parse::<usize>().map_err(|_| invalid())?)),
+        None => (range, Some(range.parse::<usize>().map_err(|_| invalid())?)),
+    };
+    let start = start.parse::<usize>().map_err(|_| invalid())?;
+    if start == 0 || end.is_some_and(|end| end < start) {
+        return Err(format!("Invalid line range '{}': lines are numbered from 1 and the end cannot come before the start", range));
+    }
+    Ok(Selection::Lines { start, end })
+}
+
+/// A file to document in single file mode, with the lines selected from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectedFile {
+    pub path: PathBuf,
+    /// Sorted, non-overlapping 1-based inclusive ranges; `None` for the whole file.
+    pub ranges: Option<Vec<(usize, usize)>>,
+}
+
+impl SelectedFile {
+    /// Cuts a file body down to the selected ranges, with a note in place of
+    /// each run of left out lines. Binary bodies are returned as they are.
+    pub fn excerpt(&self, body: FileBody) -> FileBody {
+        let (Some(ranges), FileBody::Text(text)) = (&self.ranges, &body) else {
+            return body;
+        };
+        let lines: Vec<&str> = text.lines().collect();
+        let mut excerpt = Vec::new();
+        let mut next = 1;
+        for &(start, end) in ranges {
+            if start > next {
+                excerpt.push((None, omitted_note(next, start - 1)));
+            }
+            excerpt.extend((start..=end.min(lines.len())).map(|number| (Some(number), lines[number - 1].to_string())));
+            next = end + 1;
+        }
+        if next <= lines.len() {
+            excerpt.push((None, omitted_note(next, lines.len())));
+        }
+        FileBody::Excerpt(excerpt)
+    }
+}
+
+fn omitted_note(start: usize, end: usize) -> String {
+    if start == end {
+        format!("[line {} omitted]", start)
+    } else {
+        format!("[lines {}-{} omitted]", start, end)
+    }
+}
+
+/// Groups the selectors by file, in the order the files were first named, and
+/// resolves line ranges and symbols. Naming a file without a selector selects
+/// all of it. Fails if a file is missing, a range starts past the end of its
+/// file, or a symbol is not found.
+pub fn resolve_selectors(selectors: &[FileSelector]) -> io::Result<Vec<SelectedFile>> {
+    let mut grouped: Vec<(&Path, Vec<&Selection>)> = Vec::new();
+    for selector in selectors {
+        match grouped.iter_mut().find(|(path, _)| *path == selector.path) {
+            Some((_, selections)) => selections.push(&selector.selection),
+            None => grouped.push((&selector.path, vec![&selector.selection])),
+        }
+    }
+
+    grouped.into_iter()
+        .map(|(path, selections)| {
+            if !path.is_file() {
+                return Err(io::Error::new(io::ErrorKind::NotFound, format!("The specified file does not exist: {}", path.display())));
+            }
+            if selections.contains(&&Selection::Whole) {
+                return Ok(SelectedFile { path: path.to_path_buf(), ranges: None });
+            }
+            if detect_binary(path)?.is_some() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Cannot select lines of a binary file: {}", path.display())));
+            }
+            let text = read_text_file(path)?.text;
+            let lines: Vec<&str> = text.lines().collect();
+            let mut ranges = selections.into_iter()
+                .map(|selection| resolve_selection(path, &text, &lines, selection))
+                .collect::<io::Result<Vec<_>>>()?;
+            Ok(SelectedFile { path: path.to_path_buf(), ranges: Some(merge_ranges(&mut ranges)) })
+        })
+        .collect()
+}
+
+/// Resolves a line range or symbol to 1-based inclusive lines. Files with a
+/// whole-file selector are returned by `resolve_selectors` before this runs.
+fn resolve_selection(path: &Path, text: &str, lines: &[&str], selection: &Selection) -> io::Result<(usize, usize)> {
+    match selection {
+        Selection::Whole => unreachable!("whole-file selections never have their lines resolved"),
+        Selection::Lines { start, end } => {
+            if *start > lines.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Line {} is past the end of {} ({} lines)", start, path.display(), lines.len()),
+                ));
+            }
+            Ok((*start, end.unwrap_or(lines.len()).min(lines.len())))
+        }
+        Selection::Symbol(symbol) => find_symbol(path, text, lines, symbol)
+            .map(|(start, end)| (start + 1, end + 1))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Symbol '{}' was not found in {}", symbol, path.display()))),
+    }
+}
+
+/// Sorts ranges and joins those that overlap or touch.
+fn merge_ranges(ranges: &mut [(usize, usize)]) -> Vec<(usize, usize)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for &(start, end) in ranges.iter() {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Finds the 0-based first and last line of a symbol's declaration, including
+/// the doc comments and attributes right above it. Each part of a nested
+/// symbol is looked up within the lines of the one before.
+fn find_symbol(path: &Path, text: &str, lines: &[&str], symbol: &str) -> Option<(usize, usize)> {
+    let language = detect_language(path, text);
+    let mut scope = (0, lines.len().checked_sub(1)?);
+    let mut start = 0;
+    for name in symbol.split("::").flat_map(|part| part.split('.')).filter(|name| !name.is_empty()) {
+        start = find_declaration(lines, scope, name, language)?;
+        scope = (start, declaration_end(lines, start, language));
+    }
+    Some((leading_comments_start(lines, start, language), scope.1))
+}
+
+/// The line declaring `name` within the scope: preferably one that declares
+/// exactly that name, then any declaration mentioning it, then any unindented
+/// line mentioning it (C functions, shell functions, Makefile targets).
+/// In Markdown, the heading with that text.
+fn find_declaration(lines: &[&str], (first, last): (usize, usize), name: &str, language: Option<&str>) -> Option<usize> {
+    let in_scope = || lines.iter().enumerate().take(last + 1).skip(first);
+    let find = |matches: &dyn Fn(&str) -> bool| in_scope().find(|(_, line)| matches(line)).map(|(i, _)| i);
+
+    if language == Some("markdown") {
+        let slug = |text: &str| text.to_lowercase().replace(' ', "-");
+        return find(&|line| line.starts_with('#') && {
+            let heading = line.trim_start_matches('#').trim();
+            heading.eq_ignore_ascii_case(name) || slug(heading) == name
+        });
+    }
+
+    find(&|line| is_declaration(line.trim_start()) && declared_name(line) == Some(name))
+        .or_else(|| find(&|line| is_declaration(line.trim_start()) && contains_word(line, name)))
+        .or_else(|| find(&|line| !line.starts_with(char::is_whitespace) && contains_word(line, name)))
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// The first word of a declaration that is not a keyword or modifier.
+fn declared_name(line: &str) -> Option<&str> {
+    line.split(|c: char| !is_identifier_char(c))
+        .filter(|word| !word.is_empty())
+        .find(|word| !DECLARATION_MODIFIERS.contains(word))
+}
+
+fn contains_word(line: &str, word: &str) -> bool {
+    line.match_indices(word).any(|(i, _)| {
+        let before = line[..i].chars().next_back();
+        let after = line[i + word.len()..].chars().next();
+        !before.is_some_and(is_identifier_char) && !after.is_some_and(is_identifier_char)
+    })
+}
+
+/// The last line of the declaration starting at `start`: the end of its
+/// Markdown section, of its indented block in indentation based languages,
+/// and otherwise of its braces or statement.
+fn declaration_end(lines: &[&str], start: usize, language: Option<&str>) -> usize {
+    match language {
+        Some("markdown") => section_end(lines, start),
+        Some("python" | "ruby" | "elixir" | "nim" | "haskell" | "fsharp" | "yaml") => indented_block_end(lines, start),
+        _ => braced_block_end(lines, start).unwrap_or_else(|| indented_block_end(lines, start)),
+    }
+}
+
+/// The line before the next heading of the same or a higher level, not
+/// counting blank lines.
+fn section_end(lines: &[&str], start: usize) -> usize {
+    let level = lines[start].chars().take_while(|&c| c == '#').count();
+    let next = lines.iter()
+        .enumerate()
+        .skip(start + 1)
+        .find(|(_, line)| {
+            let heading_level = line.chars().take_while(|&c| c == '#').count();
+            heading_level > 0 && heading_level <= level
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(lines.len());
+    last_non_blank(lines, start, next)
+}
+
+/// The last line that is indented deeper than `start`, or continues a
+/// bracket it opened. A closing `end` at the same indentation is included.
+fn indented_block_end(lines: &[&str], start: usize) -> usize {
+    let indent = indentation(lines[start]);
+    let mut depth = bracket_depth_change(lines[start]);
+    let mut end = start;
+    for (i, line) in lines.iter().enumerate().skip(start + 1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if depth <= 0 && indentation(line) <= indent {
+            let trimmed = line.trim();
+            if indentation(line) == indent && (trimmed == "end" || trimmed.starts_with("end ")) {
+                end = i;
+            }
+            break;
+        }
+        depth += bracket_depth_change(line);
+        end = i;
+    }
+    end
+}
+
+/// The line closing the first brace opened from `start` on, or the line
+/// ending the statement if a `;` comes first. `None` if neither is found.
+fn braced_block_end(lines: &[&str], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut opened = false;
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        for c in code_chars(line) {
+            match c {
+                '{' => {
+                    depth += 1;
+                    opened = true;
+                }
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        if opened && depth <= 0 {
+            return Some(i);
+        }
+        if !opened && line.trim_end().ends_with(';') {
+            return Some(i);
+        }
+    }
+    None
+}
+
+fn bracket_depth_change(line: &str) -> i32 {
+    code_chars(line).map(|c| match c {
+        '(' | '[' | '{' => 1,
+        ')' | ']' | '}' => -1,
+        _ => 0,
+    }).sum()
+}
+
+/// The characters of a line outside of string and character literals and
+/// `//` comments, close enough for counting brackets.
+fn code_chars(line: &str) -> impl Iterator<Item = char> + '_ {
+    let chars: Vec<char> = line.chars().collect();
+    let mut code = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '"' => {
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    i += if chars[i] == '\\' { 2 } else { 1 };
+                }
+            }
+            // Character literals such as '{' or '\'', but not Rust lifetimes
+            '\'' if chars.get(i + 2) == Some(&'\'') => i += 2,
+            '\'' if chars.get(i + 1) == Some(&'\\') && chars.get(i + 3) == Some(&'\'') => i += 3,
+            '/' if chars.get(i + 1) == Some(&'/') => break,
+            c => code.push(c),
+        }
+        i += 1;
+    }
+    code.into_iter()
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn last_non_blank(lines: &[&str], start: usize, end: usize) -> usize {
+    (start..end).rev().find(|&i| !lines[i].trim().is_empty()).unwrap_or(start)
+}
+
+/// The first of the comment, attribute and decorator lines directly above a
+/// declaration.
+fn leading_comments_start(lines: &[&str], start: usize, language: Option<&str>) -> usize {
+    if language == Some("markdown") {
+        return start;
+    }
+    let syntax = language.map(comment_syntax);
+    let is_leading = |line: &str| {
+        let line = line.trim_start();
+        ["#[", "#!", "@", "/*", "*"].iter().any(|prefix| line.starts_with(prefix))
+            || syntax.is_some_and(|syntax| syntax.line.iter().any(|prefix| line.starts_with(prefix)))
+    };
+    let mut first = start;
+    while first > 0 && !lines[first - 1].trim().is_empty() && is_leading(lines[first - 1]) {
+        first -= 1;
+    }
+    first
+}
@@ -0,0 +1,92 @@
+// split.rs
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use crate::args::Args;
+use crate::tokens::TokenCounter;
+
+/// Measures output against the size limit of `--split-tokens` or `--split-bytes`.
+pub struct PartSizer {
+    limit: usize,
+    // Counts bytes when absent
+    counter: Option<TokenCounter>,
+}
+
+impl PartSizer {
+    /// Returns a sizer if the output is to be split into parts.
+    pub fn from_args(args: &Args) -> Option<Self> {
+        match (args.split_tokens, args.split_bytes) {
+            (Some(limit), _) => Some(PartSizer { limit, counter: Some(TokenCounter::new(args.tokenizer)) }),
+            (None, Some(limit)) => Some(PartSizer { limit, counter: None }),
+            (None, None) => None,
+        }
+    }
+
+    /// Size of some rendered output in the unit of the limit.
+    pub fn measure(&self, output: &[u8]) -> usize {
+        match &self.counter {
+            Some(counter) => counter.count(&String::from_utf8_lossy(output)),
+            None => output.len(),
+        }
+    }
+
+    /// Groups consecutive file blocks of the given sizes into parts that stay
+    /// under the limit. The first part also holds `first_overhead` (header,
+    /// tree and index), every other part `overhead` (its header and footer),
+    /// and the last part `trailer` as well. A block is never split, so one
+    /// larger than the limit gets a part of its own. Ranges index into
+    /// `block_sizes`; the first and last ones may be empty.
+    pub fn pack(&self, block_sizes: &[usize], first_overhead: usize, overhead: usize, trailer: usize) -> Vec<Range<usize>> {
+        let mut parts = Vec::new();
+        let mut start = 0;
+        let mut used = first_overhead;
+
+        // A new part is only started when the current one has content; the
+        // first part always has the tree
+        for (i, &size) in block_sizes.iter().enumerate() {
+            if used + size > self.limit {
+                parts.push(start..i);
+                start = i;
+                used = overhead;
+            }
+            used += size;
+        }
+        // The trailers get a part of their own if they do not fit after the last file
+        if used + trailer > self.limit && start < block_sizes.len() {
+            parts.push(start..block_sizes.len());
+            start = block_sizes.len();
+        }
+        parts.push(start..block_sizes.len());
+        parts
+    }
+}
+
+/// Path of a numbered part: `output.txt` becomes `output.part2.txt`.
+pub fn part_path(output_file: &Path, part: usize) -> PathBuf {
+    let stem = output_file.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output_file.extension() {
+        Some(ext) => format!("{}.part{}.{}", stem, part, ext.to_string_lossy()),
+        None => format!("{}.part{}", stem, part),
+    };
+    output_file.with_file_name(name)
+}
+
+/// Returns true if `path` is a part written for `output_file` by an earlier
+/// run, so that old parts are never documented.
+pub fn is_part_file(path: &Path, output_file: &Path) -> bool {
+    if path.parent() != output_file.parent() {
+        return false;
+    }
+    let (Some(name), Some(stem)) = (path.file_name().and_then(|n| n.to_str()), output_file.file_stem().and_then(|s| s.to_str())) else {
+        return false;
+    };
+    let extension = output_file.extension().and_then(|e| e.to_str());
+
+    let Some(rest) = name.strip_prefix(stem).and_then(|rest| rest.strip_prefix(".part")) else {
+        return false;
+    };
+    let number = match extension {
+        Some(ext) => rest.strip_suffix(ext).and_then(|rest| rest.strip_suffix('.')),
+        None => Some(rest),
+    };
+    number.is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
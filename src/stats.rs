@@ -0,0 +1,166 @@
+// stats.rs
+use std::collections::BTreeMap;
+use std::path::Path;
+use serde::Serialize;
+use crate::language::{comment_syntax, detect_language, CommentSyntax};
+use crate::write::FileBody;
+
+/// Number of entries in the largest files and deepest paths lists.
+const STATS_LIST_LEN: usize = 5;
+
+/// Language name for text files whose language is not recognized.
+const OTHER_LANGUAGE: &str = "other";
+
+/// An overview of the repository's composition, shown before the file
+/// contents with `--stats`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RepoStats {
+    pub files: usize,
+    pub binary_files: usize,
+    /// Bytes on disk across all files.
+    pub total_size: u64,
+    /// Line counts per language, most code first.
+    pub languages: Vec<LanguageStats>,
+    pub largest_files: Vec<PathSize>,
+    pub deepest_paths: Vec<PathDepth>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LanguageStats {
+    pub language: String,
+    pub files: usize,
+    pub code: usize,
+    pub comments: usize,
+    pub blank: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PathSize {
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PathDepth {
+    pub path: String,
+    /// Number of path components below the repository root.
+    pub depth: usize,
+}
+
+/// Gathers `RepoStats` one file at a time, from contents that have already
+/// been read for the other passes over the files.
+#[derive(Debug, Default)]
+pub struct StatsCollector {
+    files: usize,
+    binary_files: usize,
+    total_size: u64,
+    languages: BTreeMap<&'static str, LanguageStats>,
+    sizes: Vec<PathSize>,
+    depths: Vec<PathDepth>,
+}
+
+impl StatsCollector {
+    pub fn new() -> Self {
+        StatsCollector::default()
+    }
+
+    /// Adds a walked file. Files that could not be read (`body` is `None`)
+    /// are counted by size only; the writers report them.
+    pub fn add(&mut self, path: &Path, relative_path: String, body: Option<&FileBody>) {
+        let size = path.metadata().map(|m| m.len()).unwrap_or(0);
+        self.files += 1;
+        self.total_size += size;
+
+        match body {
+            Some(FileBody::Binary(_)) => self.binary_files += 1,
+            Some(body) => {
+                let text = body.text();
+                let language = detect_language(path, &text).unwrap_or(OTHER_LANGUAGE);
+                let counts = self.languages.entry(language).or_insert_with(|| LanguageStats {
+                    language: language.to_string(),
+                    ..LanguageStats::default()
+                });
+                counts.files += 1;
+                count_lines(&text, comment_syntax(language), counts);
+            }
+            None => {}
+        }
+
+        self.depths.push(PathDepth { depth: Path::new(&relative_path).components().count(), path: relative_path.clone() });
+        self.sizes.push(PathSize { path: relative_path, size });
+    }
+
+    pub fn finish(self) -> RepoStats {
+        let mut languages: Vec<LanguageStats> = self.languages.into_values().collect();
+        languages.sort_by(|a, b| b.code.cmp(&a.code).then_with(|| a.language.cmp(&b.language)));
+
+        let mut largest_files = self.sizes;
+        largest_files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+        largest_files.truncate(STATS_LIST_LEN);
+
+        let mut deepest_paths = self.depths;
+        deepest_paths.sort_by(|a, b| b.depth.cmp(&a.depth).then_with(|| a.path.cmp(&b.path)));
+        deepest_paths.truncate(STATS_LIST_LEN);
+
+        RepoStats {
+            files: self.files,
+            binary_files: self.binary_files,
+            total_size: self.total_size,
+            languages,
+            largest_files,
+            deepest_paths,
+        }
+    }
+}
+
+impl RepoStats {
+    /// Line counts summed over all languages.
+    pub fn total_lines(&self) -> LanguageStats {
+        self.languages.iter().fold(LanguageStats { language: "total".to_string(), ..LanguageStats::default() }, |mut total, language| {
+            total.files += language.files;
+            total.code += language.code;
+            total.comments += language.comments;
+            total.blank += language.blank;
+            total
+        })
+    }
+}
+
+/// Classifies each line as code, comment or blank. A line is a comment if it
+/// starts with a comment marker or lies inside a block comment; lines that
+/// mix code and comments count as code.
+fn count_lines(text: &str, syntax: CommentSyntax, counts: &mut LanguageStats) {
+    // End marker of the block comment that is still open
+    let mut open_block: Option<&str> = None;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            counts.blank += 1;
+            continue;
+        }
+        if let Some(end) = open_block {
+            counts.comments += 1;
+            if line.contains(end) {
+                open_block = None;
+            }
+            continue;
+        }
+
+        // Block markers first, since Lua's `--[[` also starts with `--`
+        if let Some((start, end)) = syntax.block {
+            if let Some(rest) = line.strip_prefix(start) {
+                counts.comments += 1;
+                if !rest.contains(end) {
+                    open_block = Some(end);
+                }
+                continue;
+            }
+        }
+        if syntax.line.iter().any(|prefix| line.starts_with(prefix)) {
+            counts.comments += 1;
+        } else {
+            counts.code += 1;
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// tokens.rs
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tiktoken_rs::CoreBPE;
+use crate::args::Tokenizer;
+
+/// Counts tokens with one of the BPE vocabularies bundled in the binary, so
+/// no network access or model files are needed.
+pub struct TokenCounter {
+    bpe: &'static CoreBPE,
+    name: &'static str,
+}
+
+impl TokenCounter {
+    pub fn new(tokenizer: Tokenizer) -> Self {
+        match tokenizer {
+            Tokenizer::O200k => TokenCounter { bpe: tiktoken_rs::o200k_base_singleton(), name: "o200k_base" },
+            Tokenizer::Cl100k => TokenCounter { bpe: tiktoken_rs::cl100k_base_singleton(), name: "cl100k_base" },
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Counts tokens in `text`, treating special token markers as plain text.
+    pub fn count(&self, text: &str) -> usize {
+        self.bpe.encode_ordinary(text).len()
+    }
+}
+
+/// Token counts per walked path. Directories hold the sum of the files below them.
+#[derive(Debug, Default)]
+pub struct TokenCounts {
+    counts: HashMap<PathBuf, usize>,
+    files: Vec<(PathBuf, usize)>,
+}
+
+impl TokenCounts {
+    /// Adds the tokens of a text file to the file and to every directory
+    /// above it, up to `root`. Binary and unreadable files are left out; the
+    /// writers report those.
+    pub fn add(&mut self, path: &Path, root: &Path, tokens: usize) {
+        self.files.push((path.to_path_buf(), tokens));
+        for ancestor in path.ancestors() {
+            *self.counts.entry(ancestor.to_path_buf()).or_default() += tokens;
+            if ancestor == root {
+                break;
+            }
+        }
+    }
+
+    /// Tokens of a file, or of all files below a directory.
+    pub fn get(&self, path: &Path) -> Option<usize> {
+        self.counts.get(path).copied()
+    }
+
+    /// Files with their token counts, largest first.
+    pub fn largest_files(&self) -> Vec<(&Path, usize)> {
+        let mut files: Vec<_> = self.files.iter().map(|(p, t)| (p.as_path(), *t)).collect();
+        files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        files
+    }
+}
+
+/// Formats a count with thousands separators, e.g. `12,345`.
+pub fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(c);
+    }
+    formatted
+}
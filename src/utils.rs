@@ -1,99 +1,452 @@
-use std::path::Path;
-use globset::GlobSet;
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use globset::{GlobBuilder, GlobMatcher};
 use walkdir::{DirEntry, WalkDir};
-use crate::args::Args;
+use crate::args::{Args, BinaryMode, SortOrder};
+use crate::binary::detect_binary;
 use crate::config::Config;
+use crate::errors::{SkipLog, SkipReason};
+use crate::explain::IgnoreReason;
+use crate::gitignore::GitignoreFilter;
+use crate::logging::{self, debug};
+use crate::split::is_part_file;
+
+/// A glob pattern matched against repository-relative paths with forward
+/// slashes, like a gitignore pattern:
+///
+/// - A pattern without a `/`, such as `*.log`, matches a name at any depth.
+/// - A pattern with a `/` in it, such as `src/*.rs`, is anchored at the
+///   repository root, and so is one starting with `/`, such as `/docs`.
+/// - `*` and `?` never match a `/`; `**` matches any number of directories,
+///   as in `**/tests`, `docs/**` or `src/**/mod.rs`.
+/// - A trailing `/`, as in `build/`, matches directories only.
+#[derive(Debug, Clone)]
+pub struct PathPattern {
+    matcher: GlobMatcher,
+    dir_only: bool,
+}
+
+impl PathPattern {
+    pub fn new(pattern: &str, case_insensitive: bool) -> Result<Self, globset::Error> {
+        let dir_only = pattern.ends_with('/');
+        let pattern = pattern.trim_end_matches('/');
+        let anchored = match pattern.strip_prefix("./").or_else(|| pattern.strip_prefix('/')) {
+            Some(rest) => rest.to_string(),
+            None if pattern.contains('/') => pattern.to_string(),
+            None => format!("**/{}", pattern),
+        };
+        let glob = GlobBuilder::new(&anchored)
+            .literal_separator(true)
+            .case_insensitive(case_insensitive)
+            .build()?;
+        Ok(PathPattern { matcher: glob.compile_matcher(), dir_only })
+    }
+
+    /// The pattern as matched against whole repository-relative paths.
+    pub fn glob(&self) -> &str {
+        self.matcher.glob().glob()
+    }
+
+    /// Whether the pattern matches the path itself.
+    pub fn is_match(&self, relative_path: &str, is_dir: bool) -> bool {
+        (is_dir || !self.dir_only) && self.matcher.is_match(relative_path)
+    }
+
+    /// Whether the pattern matches the path or one of the directories above it.
+    pub fn is_match_within(&self, relative_path: &str, is_dir: bool) -> bool {
+        relative_path.match_indices('/').any(|(i, _)| self.is_match(&relative_path[..i], true))
+            || self.is_match(relative_path, is_dir)
+    }
+}
+
+/// Hidden paths that are documented even when hidden files are excluded.
+///
+/// Patterns are `PathPattern`s, e.g. `.github/workflows/**` or
+/// `.cargo/config.toml`. Hidden directories are traversed when a pattern
+/// could match something inside them.
+pub struct HiddenAllowlist {
+    patterns: Vec<PathPattern>,
+    components: Vec<Vec<GlobMatcher>>,
+}
+
+impl HiddenAllowlist {
+    pub fn new<'a, I: IntoIterator<Item = &'a String>>(patterns: I, case_insensitive: bool) -> io::Result<Self> {
+        let mut allowlist = HiddenAllowlist { patterns: Vec::new(), components: Vec::new() };
+
+        for pattern in patterns {
+            let pattern = pattern.trim();
+            if pattern.trim_matches(['.', '/']).is_empty() {
+                continue;
+            }
+            let invalid = |e: globset::Error| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid hidden allowlist pattern '{}': {}", pattern, e));
+
+            let pattern = PathPattern::new(pattern, case_insensitive).map_err(invalid)?;
+            allowlist.components.push(
+                pattern.glob().split('/')
+                    .map(|c| GlobBuilder::new(c).case_insensitive(case_insensitive).build().map(|g| g.compile_matcher()).map_err(invalid))
+                    .collect::<io::Result<Vec<_>>>()?,
+            );
+            allowlist.patterns.push(pattern);
+        }
+        Ok(allowlist)
+    }
+
+    /// Returns true if the relative path, or one of its ancestors, is allowlisted,
+    /// or if it is a directory that may contain an allowlisted path.
+    pub fn allows(&self, relative_path: &str, is_dir: bool) -> bool {
+        self.matches(relative_path, is_dir)
+            || is_dir && self.components.iter().any(|pattern| Self::may_contain(pattern, relative_path))
+    }
+
+    /// Returns true if a pattern matches the relative path or one of its ancestors.
+    pub fn matches(&self, relative_path: &str, is_dir: bool) -> bool {
+        self.patterns.iter().any(|pattern| pattern.is_match_within(relative_path, is_dir))
+    }
+
+    fn may_contain(pattern: &[GlobMatcher], relative_dir: &str) -> bool {
+        let mut pattern = pattern.iter();
+        for component in relative_dir.split('/') {
+            match pattern.next() {
+                Some(glob) if glob.glob().glob() == "**" => return true,
+                Some(glob) if glob.is_match(component) => {}
+                _ => return false,
+            }
+        }
+        pattern.next().is_some()
+    }
+}
+
+/// `--include` patterns, evaluated in order. Patterns starting with `!`
+/// exclude what they match. A pattern matching a directory applies to the
+/// files below it.
+pub struct IncludePatterns {
+    rules: Vec<(String, PathPattern)>,
+}
+
+impl IncludePatterns {
+    pub fn new(patterns: &[String], case_insensitive: bool) -> io::Result<Self> {
+        let rules = patterns.iter()
+            .map(|pattern| {
+                PathPattern::new(pattern.strip_prefix('!').unwrap_or(pattern), case_insensitive)
+                    .map(|glob| (pattern.clone(), glob))
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid include pattern '{}': {}", pattern, e)))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(IncludePatterns { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether a file is included, and the pattern that decided it: the last
+    /// one matching the path. Without a match, files are left out, unless
+    /// the first pattern is a negation, which makes the patterns start from
+    /// everything.
+    pub fn decide(&self, relative_path: &str) -> (bool, Option<&str>) {
+        match self.rules.iter().rev().find(|(_, glob)| glob.is_match_within(relative_path, false)) {
+            Some((pattern, _)) => (!pattern.starts_with('!'), Some(pattern)),
+            None => (self.rules.first().is_some_and(|(pattern, _)| pattern.starts_with('!')), None),
+        }
+    }
+}
+
+/// Glob patterns from `--ignore-files` and `--ignore-types`, remembered so
+/// that the pattern excluding a path can be reported, the `--exclude-dir`
+/// entries that are paths rather than names, and the `--include` patterns.
+pub struct IgnoreGlobs {
+    patterns: Vec<(&'static str, String, PathPattern)>,
+    exclude_dirs: Vec<(String, PathPattern)>,
+    pub includes: IncludePatterns,
+}
+
+impl IgnoreGlobs {
+    pub fn new(args: &Args) -> io::Result<Self> {
+        let mut patterns = Vec::new();
+
+        // Process ignore_files as exact glob patterns
+        for pattern in args.ignore_files.iter().filter(|p| !p.is_empty()) {
+            let glob = PathPattern::new(pattern, args.glob_case_insensitive)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid ignore file pattern '{}': {}", pattern, e)))?;
+            patterns.push(("--ignore-files", pattern.clone(), glob));
+        }
+
+        // Process ignore_types by trimming leading dots and creating *.ext globs
+        for pattern in &args.ignore_types {
+            let trimmed = pattern.trim_start_matches('.');
+            if trimmed.is_empty() {
+                continue;
+            }
+            let glob_pattern = format!("*.{}", trimmed);
+            let glob = PathPattern::new(&glob_pattern, args.glob_case_insensitive)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid ignore type pattern '{}': {}", pattern, e)))?;
+            patterns.push(("--ignore-types", glob_pattern, glob));
+        }
+
+        // Exclude-dir entries with a slash are paths or globs; bare names are compared as they are
+        let mut exclude_dirs = Vec::new();
+        for pattern in args.exclude_dir.iter().filter(|p| p.contains('/')) {
+            let glob = PathPattern::new(pattern, args.glob_case_insensitive)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid exclude dir pattern '{}': {}", pattern, e)))?;
+            exclude_dirs.push((pattern.clone(), glob));
+        }
+
+        Ok(IgnoreGlobs { patterns, exclude_dirs, includes: IncludePatterns::new(&args.include, args.glob_case_insensitive)? })
+    }
+
+    /// Returns the option and pattern of the first glob matching the
+    /// repository-relative path.
+    pub fn matching(&self, relative_path: &str, is_dir: bool) -> Option<(&'static str, &str)> {
+        self.patterns.iter()
+            .find(|(_, _, glob)| glob.is_match(relative_path, is_dir))
+            .map(|(option, pattern, _)| (*option, pattern.as_str()))
+    }
+
+    /// Returns the `--exclude-dir` path or glob matching the repository-relative
+    /// path of a directory.
+    pub fn excluded_dir(&self, relative_path: &str) -> Option<&str> {
+        self.exclude_dirs.iter()
+            .find(|(_, glob)| glob.is_match(relative_path, true))
+            .map(|(pattern, _)| pattern.as_str())
+    }
+}
+
+/// Returns the path relative to `root` with forward slashes.
+pub fn relative_path_string(path: &Path, root: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Resolves `--include-dir` paths, relative to the repository unless they are
+/// absolute, to canonical paths relative to the canonical repository root.
+/// Fails if a directory does not exist or lies outside the repository.
+pub fn resolve_include_dirs(repo_path: &Path, dirs: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let root = repo_path.canonicalize().map_err(|_| {
+        io::Error::new(io::ErrorKind::NotFound, format!("The specified directory does not exist or is not a directory: {}", repo_path.display()))
+    })?;
+    dirs.iter()
+        .map(|dir| {
+            let canonical = repo_path.join(dir).canonicalize().ok().filter(|path| path.is_dir()).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("The include directory does not exist or is not a directory: {}", dir.display()))
+            })?;
+            canonical.strip_prefix(&root).map(Path::to_path_buf).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("The include directory is outside the repository: {}", dir.display()))
+            })
+        })
+        .collect()
+}
+
+/// A walked path relative to the repository, comparable with the resolved
+/// `--include-dir` paths.
+fn relative_to_repo(path: &Path, args: &Args) -> PathBuf {
+    path.strip_prefix(&args.repo_path)
+        .unwrap_or(path)
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
 
 pub fn is_ignored(
     entry: &DirEntry,
-    globset: &Arc<GlobSet>,
+    globs: &IgnoreGlobs,
+    hidden_allowlist: &HiddenAllowlist,
     args: &Args,
-    _config: &Config,
+    config: &Config,
     output_file_path: &Path
 ) -> bool {
+    ignore_reason(entry, globs, hidden_allowlist, args, config, output_file_path).is_some()
+}
+
+/// Returns the first rule that excludes the entry, or `None` if it is
+/// documented. Gitignore rules are checked separately by `GitignoreFilter`.
+pub fn ignore_reason(
+    entry: &DirEntry,
+    globs: &IgnoreGlobs,
+    hidden_allowlist: &HiddenAllowlist,
+    args: &Args,
+    _config: &Config,
+    output_file_path: &Path
+) -> Option<IgnoreReason> {
     let path = entry.path();
     
-    // Check output file using canonical path
+    // Check output file, and parts of split output, using canonical path
     if let Ok(canonical_path) = path.canonicalize() {
         if canonical_path == output_file_path {
-            return true;
+            return Some(IgnoreReason::OutputFile);
+        }
+        if is_part_file(&canonical_path, output_file_path) {
+            return Some(IgnoreReason::OutputPart);
         }
     }
     
     // Handle include_dir first - if specified, check both containment and ancestry
-    if let Some(include_dir) = &args.include_dir {
+    if !args.include_dir.is_empty() {
         // Allow paths that are either:
-        // 1. Inside the include_dir
-        // 2. Are ancestors of include_dir (needed to traverse to it)
-        if !path.starts_with(include_dir) && !include_dir.starts_with(path) {
-            return true;
+        // 1. Inside one of the include dirs
+        // 2. Ancestors of one of them (needed to traverse to it)
+        let relative_path = relative_to_repo(path, args);
+        if !args.include_dir.iter().any(|dir| relative_path.starts_with(dir) || dir.starts_with(&relative_path)) {
+            return Some(IgnoreReason::OutsideIncludeDir(args.include_dir.clone()));
         }
     }
 
     // Check depth
     if entry.depth() > args.max_depth {
-        return true;
+        return Some(IgnoreReason::MaxDepth(args.max_depth));
     }
 
-    // Skip excluded directories
+    // Skip hidden files and directories; .git is never documented
+    if entry.depth() > 0 {
+        let file_name = entry.file_name().to_string_lossy();
+        if file_name == ".git" {
+            return Some(IgnoreReason::GitDir);
+        }
+        if !args.include_hidden {
+            let relative_path = relative_path_string(path, &args.repo_path);
+            if is_hidden_path(&relative_path) && !hidden_allowlist.allows(&relative_path, entry.file_type().is_dir()) {
+                return Some(IgnoreReason::Hidden);
+            }
+        }
+    }
+
+    // Skip excluded directories, by name anywhere or by path from the root
     if path.is_dir() {
         if let Some(dir_name) = path.file_name().and_then(|n| n.to_str()) {
             if args.exclude_dir.contains(dir_name) {
-                return true;
+                return Some(IgnoreReason::ExcludeDir(dir_name.to_string()));
             }
         }
+        if let Some(pattern) = globs.excluded_dir(&relative_path_string(path, &args.repo_path)).filter(|_| entry.depth() > 0) {
+            return Some(IgnoreReason::ExcludeDir(pattern.to_string()));
+        }
     }
 
     // Handle file-specific filters for non-directory entries
     if path.is_file() {
+        // Check the include patterns; directories are always traversed
+        if !globs.includes.is_empty() {
+            if let (false, pattern) = globs.includes.decide(&relative_path_string(path, &args.repo_path)) {
+                return Some(IgnoreReason::NotIncluded(pattern.map(str::to_string)));
+            }
+        }
+
         // Check file name against ignore_files
         if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
             if args.ignore_files.contains(file_name) {
-                return true;
+                return Some(IgnoreReason::IgnoreFile(file_name.to_string()));
             }
         }
 
         // Check extensions against ignore_types
         if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
             if args.ignore_types.contains(&ext.to_lowercase()) {
-                return true;
+                return Some(IgnoreReason::IgnoreType(ext.to_lowercase()));
             }
         }
 
-        // Handle settings files
-        if args.ignore_settings {
+        // Handle settings files; naming a file in the hidden allowlist keeps it,
+        // since hidden directories such as .github hold mostly YAML
+        if args.ignore_settings && !hidden_allowlist.matches(&relative_path_string(path, &args.repo_path), false) {
             if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
                 if ext.eq_ignore_ascii_case("json") || 
                    ext.eq_ignore_ascii_case("yaml") || 
                    ext.eq_ignore_ascii_case("yml") {
-                    return true;
+                    return Some(IgnoreReason::Settings(ext.to_string()));
                 }
             }
         }
+
+        // Skip binary files when requested; unreadable files are left for the writer to report
+        if args.binary_files == BinaryMode::Skip {
+            if let Ok(Some(info)) = detect_binary(path) {
+                return Some(IgnoreReason::Binary(info.to_string()));
+            }
+        }
     }
 
-    // Check against globset patterns
-    if !globset.is_empty() && globset.is_match(path) {
-        return true;
+    // Check against glob patterns; the root itself is never matched
+    if entry.depth() == 0 {
+        return None;
     }
+    globs.matching(&relative_path_string(path, &args.repo_path), entry.file_type().is_dir())
+        .map(|(option, pattern)| IgnoreReason::Pattern { option, pattern: pattern.to_string() })
+}
 
-    false
+/// Orders siblings deterministically, comparing names byte-wise rather than
+/// relying on the order the filesystem returns them in.
+pub fn compare_entries(a: &DirEntry, b: &DirEntry, sort: SortOrder) -> std::cmp::Ordering {
+    let by_name = a.file_name().cmp(b.file_name());
+    match sort {
+        SortOrder::DirsFirst => b.file_type().is_dir().cmp(&a.file_type().is_dir()).then(by_name),
+        SortOrder::Alphabetical => by_name,
+    }
 }
 
 pub fn walk_entries(
     path: &Path,
     args: &Args,
     config: &Config,
-    globset: Arc<GlobSet>,
-    output_file_path: &Path
+    globs: &IgnoreGlobs,
+    hidden_allowlist: &HiddenAllowlist,
+    output_file_path: &Path,
+    skipped: &mut SkipLog
 ) -> Vec<DirEntry> {
-    WalkDir::new(path)
+    let mut gitignore = args.use_gitignore.then(|| GitignoreFilter::new(path));
+    let sort = args.sort;
+
+    let walker = WalkDir::new(path)
         .min_depth(0)
         .max_depth(args.max_depth)
         .follow_links(args.follow_symlinks)
+        .sort_by(move |a, b| compare_entries(a, b, sort))
         .into_iter()
-        .filter_entry(|e| !is_ignored(e, &globset, args, config, output_file_path))
-        .filter_map(|e| e.ok())
-        .collect()
+        .filter_entry(|e| {
+            !is_ignored(e, globs, hidden_allowlist, args, config, output_file_path)
+                && !gitignore.as_mut().is_some_and(|g| g.is_ignored(e))
+        });
+
+    let mut entries = Vec::new();
+    for result in walker {
+        logging::entry_scanned();
+        match result {
+            Ok(entry) if entry.path_is_symlink() && !entry.path().exists() => {
+                skipped.record(entry.path(), SkipReason::BrokenSymlink, "target does not exist");
+            }
+            Ok(entry) => entries.push(entry),
+            Err(e) => skipped.record_walk(&e),
+        }
+    }
+    if !globs.includes.is_empty() {
+        remove_empty_dirs(&mut entries, |_| true);
+    } else if !args.include_hidden {
+        // A pattern such as `.env` could match inside any hidden directory, so
+        // all of them are walked; those without an allowlisted file are dropped
+        remove_empty_dirs(&mut entries, |e| {
+            let relative_path = relative_path_string(e.path(), &args.repo_path);
+            is_hidden_path(&relative_path) && !hidden_allowlist.matches(&relative_path, true)
+        });
+    }
+    debug!("walked {} entries under {}", entries.len(), path.display());
+    entries
+}
+
+/// Drops directories below the root for which `removable` holds and that
+/// contain nothing but other such directories, so that include patterns and
+/// the hidden allowlist do not leave a tree of empty directories behind.
+fn remove_empty_dirs(entries: &mut Vec<DirEntry>, removable: impl Fn(&DirEntry) -> bool) {
+    let non_empty: HashSet<PathBuf> = entries.iter()
+        .filter(|e| !e.file_type().is_dir())
+        .flat_map(|e| e.path().ancestors().skip(1).map(Path::to_path_buf))
+        .collect();
+    entries.retain(|e| e.depth() == 0 || !e.file_type().is_dir() || non_empty.contains(e.path()) || !removable(e));
+}
+
+/// Whether any component of a relative path is hidden.
+fn is_hidden_path(relative_path: &str) -> bool {
+    relative_path.split('/').any(|c| c.starts_with('.') && c != "." && c != "..")
 }
\ No newline at end of file
@@ -1,43 +1,830 @@
 // write.rs
-use std::fs::File;
-use std::io::{self, Write, BufRead, BufReader};
+use std::borrow::Cow;
+use std::io::{self, Write};
 use std::path::Path;
 use walkdir::DirEntry;
-use crate::args::Args;
+use crate::args::{Args, OutputFormat};
+use crate::binary::{detect_binary, format_size, BinaryInfo};
+use crate::budget::{BudgetPlan, Fit};
+use crate::document::{budget_record, file_record_from_body, skipped_records, Document, JsonlRecord};
+use crate::encoding::read_text_file;
+use crate::errors::{SkipLog, SkipReason};
+use crate::language::detect_language;
+use crate::logging;
+use crate::selection::SelectedFile;
+use crate::stats::{LanguageStats, RepoStats};
+use crate::tokens::{format_count, TokenCounts};
+use crate::utils::relative_path_string;
 
-pub fn write_tree(entries: &[DirEntry], output_file: &mut File) -> io::Result<()> {
+/// Writes the document header. For split output, `part` is the number of
+/// this part and the total number of parts.
+pub fn write_header<W: Write>(output_file: &mut W, args: &Args, part: Option<(usize, usize)>) -> io::Result<()> {
+    let title = match part {
+        Some((number, total)) => format!("Repository Documentation (part {} of {})", number, total),
+        None => "Repository Documentation".to_string(),
+    };
+    // Only the first part has the tree and the index of parts
+    let later_part = part.is_some_and(|(number, _)| number > 1);
+
+    match args.output_format {
+        OutputFormat::Text => {
+            writeln!(output_file, "{}", title)?;
+            if later_part {
+                writeln!(output_file, "The directory tree and an index of all parts are in part 1.")?;
+            }
+            writeln!(output_file, "This document provides an overview of the repository's structure and contents.")?;
+            writeln!(output_file, "The 'Directory/File Tree' section displays the repository's hierarchy.")?;
+            writeln!(output_file, "The 'File Content' section details the contents of each file.")?;
+            writeln!(output_file, "File contents are marked with '[File Begins]' and '[File Ends]' tags.\n")?;
+        },
+        OutputFormat::Markdown => {
+            writeln!(output_file, "# {}\n", title)?;
+            if later_part {
+                writeln!(output_file, "The directory tree and an index of all parts are in part 1.\n")?;
+            }
+            writeln!(output_file, "This document provides an overview of the repository's structure and contents.")?;
+            writeln!(output_file, "The 'Directory/File Tree' section displays the repository's hierarchy.")?;
+            writeln!(output_file, "The 'File Content' section details the contents of each file, each in its own fenced code block.\n")?;
+        },
+        OutputFormat::HTML => {
+            writeln!(output_file, "<!DOCTYPE html>")?;
+            writeln!(output_file, "<html lang=\"en\">")?;
+            writeln!(output_file, "<head>")?;
+            writeln!(output_file, "    <meta charset=\"UTF-8\">")?;
+            writeln!(output_file, "    <meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">")?;
+            writeln!(output_file, "    <title>{}</title>", title)?;
+            writeln!(output_file, "    <style>")?;
+            writeln!(output_file, "        body {{ font-family: sans-serif; margin: 2em; }}")?;
+            writeln!(output_file, "        .tree, .tree ul {{ list-style: none; padding-left: 1.5em; font-family: monospace; }}")?;
+            writeln!(output_file, "        pre {{ background: #f6f8fa; padding: 1em; overflow-x: auto; }}")?;
+            if args.line_numbers {
+                writeln!(output_file, "        pre.numbered .line::before {{ content: attr(data-line); display: inline-block; min-width: 3em; padding-right: 1em; text-align: right; color: #8c959f; user-select: none; }}")?;
+            }
+            writeln!(output_file, "    </style>")?;
+            writeln!(output_file, "</head>")?;
+            writeln!(output_file, "<body>")?;
+            writeln!(output_file, "    <h1>{}</h1>", title)?;
+            if later_part {
+                writeln!(output_file, "    <p>The directory tree and an index of all parts are in part 1.</p>")?;
+            }
+            writeln!(output_file, "    <p>This document provides an overview of the repository's structure and contents. The 'Directory/File Tree' section displays the repository's hierarchy and links to the 'File Content' section, which details the contents of each file.</p>")?;
+        },
+        OutputFormat::Xml => {
+            match part {
+                Some((number, total)) => writeln!(output_file, "<documents part=\"{}\" parts=\"{}\">", number, total)?,
+                None => writeln!(output_file, "<documents>")?,
+            }
+        },
+        // JSON documents and records are self-describing and have no header
+        OutputFormat::Json | OutputFormat::Jsonl => {},
+    }
+    Ok(())
+}
+
+/// Closes the elements opened by the header.
+pub fn write_footer<W: Write>(output_file: &mut W, args: &Args) -> io::Result<()> {
+    match args.output_format {
+        OutputFormat::HTML => writeln!(output_file, "</body>\n</html>"),
+        OutputFormat::Xml => writeln!(output_file, "</documents>"),
+        _ => Ok(()),
+    }
+}
+
+/// Writes the directory tree section in the selected output format. Entries
+/// are annotated with their token counts when `token_counts` is given.
+pub fn write_tree_section<W: Write>(entries: &[DirEntry], output_file: &mut W, args: &Args, token_counts: Option<&TokenCounts>) -> io::Result<()> {
+    match args.output_format {
+        OutputFormat::Text => {
+            writeln!(output_file, "Directory/File Tree Begins -->\n")?;
+            write_tree(entries, output_file, token_counts)?;
+            writeln!(output_file, "\n<-- Directory/File Tree Ends")?;
+        },
+        OutputFormat::Markdown => {
+            writeln!(output_file, "## Directory/File Tree\n")?;
+            let mut tree = Vec::new();
+            write_tree(entries, &mut tree, token_counts)?;
+            let tree = String::from_utf8_lossy(&tree);
+            let fence = markdown_fence(&tree);
+            writeln!(output_file, "{}text\n{}{}\n", fence, tree, fence)?;
+        },
+        OutputFormat::HTML => {
+            writeln!(output_file, "    <h2>Directory/File Tree</h2>")?;
+            write_html_tree(entries, output_file, args, token_counts)?;
+        },
+        OutputFormat::Xml => {
+            let mut tree = Vec::new();
+            write_tree(entries, &mut tree, token_counts)?;
+            write_xml_document(output_file, 1, "Directory/File Tree", &String::from_utf8_lossy(&tree))?;
+        },
+        // JSON Lines has no tree record
+        OutputFormat::Jsonl => {},
+        OutputFormat::Json => unreachable!("JSON output is written as a whole by write_json_document"),
+    }
+    Ok(())
+}
+
+/// Writes the file content section in the selected output format and returns
+/// the number of files written. With a `budget`, files are shortened or left
+/// out as planned.
+///
+/// `document_index` is the XML document index of the first file.
+pub fn write_contents_section<W: Write>(entries: &[DirEntry], output_file: &mut W, args: &Args, budget: Option<&BudgetPlan>, document_index: usize, skipped: &mut SkipLog) -> io::Result<usize> {
+    write_contents_start(output_file, args)?;
+    let written = write_file_contents(entries, output_file, args, budget, document_index, skipped)?;
+    write_contents_end(output_file, args)?;
+    Ok(written)
+}
+
+/// Writes the heading or opening marker of the file content section.
+pub fn write_contents_start<W: Write>(output_file: &mut W, args: &Args) -> io::Result<()> {
+    match args.output_format {
+        OutputFormat::Text => writeln!(output_file, "\nFile Content Begins -->\n"),
+        OutputFormat::Markdown => writeln!(output_file, "## File Content\n"),
+        OutputFormat::HTML => writeln!(output_file, "    <h2>File Content</h2>"),
+        OutputFormat::Xml | OutputFormat::Jsonl => Ok(()),
+        OutputFormat::Json => unreachable!("JSON output is written as a whole by write_json_document"),
+    }
+}
+
+/// Writes the closing marker of the file content section, if the format has one.
+pub fn write_contents_end<W: Write>(output_file: &mut W, args: &Args) -> io::Result<()> {
+    match args.output_format {
+        OutputFormat::Text => writeln!(output_file, "\n<-- File Content Ends\n"),
+        _ => Ok(()),
+    }
+}
+
+pub fn write_tree<W: Write>(entries: &[DirEntry], output_file: &mut W, token_counts: Option<&TokenCounts>) -> io::Result<()> {
+    let last_sibling = last_sibling_flags(entries);
+    // Whether the ancestor at each depth was the last of its siblings
+    let mut ancestors_last: Vec<bool> = Vec::new();
+
+    for (entry, &is_last) in entries.iter().zip(&last_sibling) {
+        let depth = entry.depth();
+        ancestors_last.truncate(depth);
+
+        let mut prefix = String::new();
+        if depth > 0 {
+            for &ancestor_last in ancestors_last.iter().skip(1) {
+                prefix.push_str(if ancestor_last { "    " } else { "│   " });
+            }
+            prefix.push_str(if is_last { "└── " } else { "├── " });
+        }
+        ancestors_last.push(is_last);
+
+        let file_name = entry.file_name().to_string_lossy();
+        writeln!(output_file, "{}{}{}", prefix, file_name, tree_annotation(entry, token_counts))?;
+    }
+    Ok(())
+}
+
+/// Text shown after an entry's name in the tree: a binary placeholder or, if
+/// requested, the entry's token count.
+fn tree_annotation(entry: &DirEntry, token_counts: Option<&TokenCounts>) -> String {
+    if let Some(placeholder) = binary_placeholder(entry) {
+        return format!(" {}", placeholder);
+    }
+    token_counts
+        .and_then(|counts| counts.get(entry.path()))
+        .map(|tokens| format!(" ({} tokens)", format_count(tokens)))
+        .unwrap_or_default()
+}
+
+/// For entries in walk (pre-)order, returns whether each one is the last
+/// child of its parent.
+fn last_sibling_flags(entries: &[DirEntry]) -> Vec<bool> {
+    let mut flags = vec![false; entries.len()];
+    // Whether a later sibling has been seen at each depth, scanning backwards
+    let mut sibling_follows: Vec<bool> = Vec::new();
+
+    for (i, entry) in entries.iter().enumerate().rev() {
+        let depth = entry.depth();
+        sibling_follows.resize(depth + 1, false);
+        flags[i] = !sibling_follows[depth];
+        sibling_follows[depth] = true;
+    }
+    flags
+}
+
+/// Writes the tree as nested lists whose file entries link to the matching
+/// file sections.
+pub fn write_html_tree<W: Write>(entries: &[DirEntry], output_file: &mut W, args: &Args, token_counts: Option<&TokenCounts>) -> io::Result<()> {
+    // Depths of the directories whose nested list is still open
+    let mut open_dirs: Vec<usize> = Vec::new();
+
+    writeln!(output_file, "    <ul class=\"tree\">")?;
     for entry in entries {
         let depth = entry.depth();
-        let prefix = "    ".repeat(depth);
-        let marker = if depth > 0 { "└── " } else { "" };
-        
-        if let Some(file_name) = entry.file_name().to_str() {
-            writeln!(output_file, "{}{}{}", prefix, marker, file_name)?;
+        while open_dirs.last().is_some_and(|&open| open >= depth) {
+            open_dirs.pop();
+            writeln!(output_file, "{}</ul></li>", html_indent(open_dirs.len()))?;
+        }
+
+        let indent = html_indent(open_dirs.len());
+        let file_name = escape_html(&entry.file_name().to_string_lossy());
+        let annotation = escape_html(&tree_annotation(entry, token_counts));
+        if entry.file_type().is_dir() {
+            writeln!(output_file, "{}<li>{}/{}<ul>", indent, file_name, annotation)?;
+            open_dirs.push(depth);
+        } else {
+            let relative_path = entry.path().strip_prefix(&args.repo_path).unwrap_or(entry.path());
+            writeln!(output_file, "{}<li><a href=\"#{}\">{}</a>{}</li>", indent, html_anchor(relative_path), file_name, annotation)?;
         }
     }
+    while open_dirs.pop().is_some() {
+        writeln!(output_file, "{}</ul></li>", html_indent(open_dirs.len()))?;
+    }
+    writeln!(output_file, "    </ul>")?;
     Ok(())
 }
 
-pub fn write_file_contents(entries: &[DirEntry], output_file: &mut File, args: &Args) -> io::Result<()> {
+fn html_indent(level: usize) -> String {
+    "    ".repeat(level + 2)
+}
+
+/// Builds a unique, whitespace-free element id for a file path.
+///
+/// Letters, digits, `.`, `-` and `/` are kept; every other byte is written as
+/// `_` followed by its hex value, so distinct paths always get distinct ids.
+pub fn html_anchor(relative_path: &Path) -> String {
+    let path = relative_path.to_string_lossy().replace('\\', "/");
+    let mut anchor = String::from("file-");
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'.' | b'-' | b'/') {
+            anchor.push(byte as char);
+        } else {
+            anchor.push_str(&format!("_{:02x}", byte));
+        }
+    }
+    anchor
+}
+
+/// Writes every readable file that fits the budget and returns how many were written.
+pub fn write_file_contents<W: Write>(entries: &[DirEntry], output_file: &mut W, args: &Args, budget: Option<&BudgetPlan>, document_index: usize, skipped: &mut SkipLog) -> io::Result<usize> {
+    let mut written = 0;
     for entry in entries.iter().filter(|e| e.file_type().is_file()) {
-        let path = entry.path();
-        let relative_path = path.strip_prefix(&args.repo_path)
-            .unwrap_or(path)
-            .to_path_buf();
+        if write_file_block(entry.path(), output_file, args, budget, document_index + written, skipped)? {
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+/// Writes one file of the content section. Returns false, without writing
+/// anything, if the file could not be read or was dropped by the budget.
+///
+/// `document_index` is the index of the file's document in the XML format.
+pub fn write_file_block<W: Write>(path: &Path, output_file: &mut W, args: &Args, budget: Option<&BudgetPlan>, document_index: usize, skipped: &mut SkipLog) -> io::Result<bool> {
+    // Read before writing any markers so unreadable files are left out entirely
+    let Some(body) = read_file_body(path, skipped) else {
+        return Ok(false);
+    };
+    logging::file_written(path);
+    let (body, fit) = match budget {
+        Some(plan) => match plan.apply(path, body) {
+            Some(body) => (body, plan.fit(path)),
+            None => return Ok(false),
+        },
+        None => (body, Fit::Full),
+    };
+
+    let name = relative_path_string(path, &args.repo_path);
+    write_named_file(path, &name, body, fit, output_file, args, args.line_numbers, document_index, skipped)
+}
+
+/// Writes the files and lines selected with `--file-path` as a file content
+/// section and returns how many files were written. Selected lines are always
+/// numbered, so the excerpts can be placed in their files.
+pub fn write_selected_files<W: Write>(files: &[SelectedFile], output_file: &mut W, args: &Args, skipped: &mut SkipLog) -> io::Result<usize> {
+    write_contents_start(output_file, args)?;
+    let mut written = 0;
+    for file in files {
+        let Some(body) = read_file_body(&file.path, skipped) else {
+            continue;
+        };
+        let line_numbers = args.line_numbers || file.ranges.is_some();
+        let name = file.path.display().to_string();
+        if write_named_file(&file.path, &name, file.excerpt(body), Fit::Full, output_file, args, line_numbers, written + 1, skipped)? {
+            written += 1;
+        }
+    }
+    write_contents_end(output_file, args)?;
+    Ok(written)
+}
+
+/// Writes a file body between markers naming the file as `name`.
+#[allow(clippy::too_many_arguments)]
+fn write_named_file<W: Write>(
+    path: &Path,
+    name: &str,
+    body: FileBody,
+    fit: Fit,
+    output_file: &mut W,
+    args: &Args,
+    line_numbers: bool,
+    document_index: usize,
+    skipped: &mut SkipLog
+) -> io::Result<bool> {
+    match args.output_format {
+        OutputFormat::Text => {
+            writeln!(output_file, "[File Begins] {}", name)?;
+            write_file_body(&body, output_file, line_numbers)?;
+            writeln!(output_file, "[File Ends] {}", name)?;
+        },
+        OutputFormat::Markdown => {
+            writeln!(output_file, "### `{}`\n", name)?;
+            write_markdown_file_body(path, &body, output_file, line_numbers)?;
+        },
+        OutputFormat::HTML => {
+            writeln!(output_file, "    <section class=\"file\" id=\"{}\">", html_anchor(Path::new(name)))?;
+            writeln!(output_file, "        <h3>{}</h3>", escape_html(name))?;
+            write_html_file_body(&body, output_file, line_numbers)?;
+            writeln!(output_file, "    </section>")?;
+        },
+        OutputFormat::Xml => {
+            write_xml_document(output_file, document_index, name, &file_body_text(&body, line_numbers))?;
+        },
+        OutputFormat::Jsonl => {
+            return write_jsonl_record(path, name, body, fit, output_file, skipped);
+        },
+        OutputFormat::Json => unreachable!("JSON output is written as a whole by write_json_document"),
+    }
+    Ok(true)
+}
+
+fn binary_placeholder(entry: &DirEntry) -> Option<String> {
+    if !entry.file_type().is_file() {
+        return None;
+    }
+    detect_binary(entry.path()).ok().flatten().map(|info| info.to_string())
+}
+
+/// Contents of a file as it will be documented.
+pub enum FileBody {
+    Binary(BinaryInfo),
+    Text(String),
+    /// Some lines of a text file with their 1-based line numbers in the file.
+    /// Notes on what was left out have no number.
+    Excerpt(Vec<(Option<usize>, String)>),
+}
+
+impl FileBody {
+    /// The text as written without line numbers; a binary file's placeholder.
+    pub fn text(&self) -> Cow<'_, str> {
+        match self {
+            FileBody::Binary(info) => Cow::Owned(info.to_string()),
+            FileBody::Text(text) => Cow::Borrowed(text),
+            FileBody::Excerpt(lines) => Cow::Owned(lines.iter().map(|(_, line)| format!("{}\n", line)).collect()),
+        }
+    }
+
+    pub fn into_text(self) -> String {
+        match self {
+            FileBody::Text(text) => text,
+            body => body.text().into_owned(),
+        }
+    }
+
+    /// The lines of a text body, each with its line number in the file; the
+    /// notes of an excerpt have none. Binary bodies have no lines.
+    pub fn numbered_lines(&self) -> Vec<(Option<usize>, &str)> {
+        match self {
+            FileBody::Binary(_) => Vec::new(),
+            FileBody::Text(text) => text.lines().enumerate().map(|(i, line)| (Some(i + 1), line)).collect(),
+            FileBody::Excerpt(lines) => lines.iter().map(|(number, line)| (*number, line.as_str())).collect(),
+        }
+    }
+}
+
+/// Prefixes a line with its number, right-aligned to `width` digits.
+pub fn number_line(number: usize, width: usize, line: &str) -> String {
+    if line.is_empty() {
+        format!("{:>width$} |", number)
+    } else {
+        format!("{:>width$} | {}", number, line)
+    }
+}
+
+/// Digits needed for the largest line number among the lines.
+pub fn line_number_width(lines: &[(Option<usize>, &str)]) -> usize {
+    lines.iter().filter_map(|(number, _)| *number).max().unwrap_or(0).to_string().len()
+}
+
+/// The lines of a text body as written, numbered if `line_numbers` is set.
+fn body_lines(body: &FileBody, line_numbers: bool) -> Vec<Cow<'_, str>> {
+    let lines = body.numbered_lines();
+    let width = line_number_width(&lines);
+    lines.into_iter()
+        .map(|(number, line)| match number {
+            Some(number) if line_numbers => Cow::Owned(number_line(number, width, line)),
+            _ => Cow::Borrowed(line),
+        })
+        .collect()
+}
+
+/// Reads a file for documentation, recording any problem in `skipped`.
+///
+/// Returns `None` if the file could not be read at all. Lossy decoding still
+/// returns the text but is recorded as a decode error.
+pub fn read_file_body(file_path: &Path, skipped: &mut SkipLog) -> Option<FileBody> {
+    let result = detect_binary(file_path).and_then(|binary| match binary {
+        Some(info) => Ok(FileBody::Binary(info)),
+        None => read_text_file(file_path).map(|decoded| {
+            if decoded.lossy {
+                skipped.record(file_path, SkipReason::DecodeError, format!("not valid {}; undecodable bytes were replaced", decoded.encoding));
+            }
+            FileBody::Text(decoded.text)
+        }),
+    });
+
+    match result {
+        Ok(body) => Some(body),
+        Err(e) => {
+            skipped.record_io(file_path, &e);
+            None
+        }
+    }
+}
+
+fn write_file_body<W: Write>(body: &FileBody, output_file: &mut W, line_numbers: bool) -> io::Result<()> {
+    match body {
+        FileBody::Binary(info) => writeln!(output_file, "{}", info)?,
+        _ => {
+            for line in body_lines(body, line_numbers) {
+                writeln!(output_file, "{}", line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_markdown_file_body<W: Write>(file_path: &Path, body: &FileBody, output_file: &mut W, line_numbers: bool) -> io::Result<()> {
+    match body {
+        FileBody::Binary(info) => writeln!(output_file, "{}\n", info),
+        _ => {
+            let text = body.text();
+            let fence = markdown_fence(&text);
+            let language = detect_language(file_path, &text).unwrap_or("");
+            writeln!(output_file, "{}{}", fence, language)?;
+            write_file_body(body, output_file, line_numbers)?;
+            writeln!(output_file, "{}\n", fence)
+        }
+    }
+}
+
+/// Returns a backtick fence longer than any backtick run in `text`, so the
+/// content can never close the code block early.
+fn markdown_fence(text: &str) -> String {
+    let longest_run = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest_run.max(2) + 1)
+}
+
+/// Writes a file body as a `<pre>` block. With `line_numbers`, every line is
+/// a span whose `data-line` attribute holds its number, which the page's
+/// style sheet shows in a right-aligned gutter.
+fn write_html_file_body<W: Write>(body: &FileBody, output_file: &mut W, line_numbers: bool) -> io::Result<()> {
+    match body {
+        FileBody::Binary(info) => writeln!(output_file, "        <p class=\"binary\">{}</p>", escape_html(&info.to_string())),
+        _ if line_numbers => {
+            let lines: Vec<String> = body.numbered_lines().into_iter()
+                .map(|(number, line)| match number {
+                    Some(number) => format!("<span class=\"line\" data-line=\"{}\">{}</span>", number, escape_html(line)),
+                    None => format!("<span class=\"note\">{}</span>", escape_html(line)),
+                })
+                .collect();
+            writeln!(output_file, "        <pre class=\"numbered\"><code>{}</code></pre>", lines.join("\n"))
+        },
+        _ => writeln!(output_file, "        <pre><code>{}</code></pre>", escape_html(body.text().trim_end_matches(['\r', '\n']))),
+    }
+}
+
+pub fn write_file_content<W: Write>(file_path: &Path, output_file: &mut W, args: &Args, skipped: &mut SkipLog) -> io::Result<()> {
+    if let Some(body) = read_file_body(file_path, skipped) {
+        match args.output_format {
+            OutputFormat::Text => write_file_body(&body, output_file, args.line_numbers)?,
+            OutputFormat::Markdown => write_markdown_file_body(file_path, &body, output_file, args.line_numbers)?,
+            OutputFormat::HTML => write_html_file_body(&body, output_file, args.line_numbers)?,
+            OutputFormat::Xml => write_xml_document(output_file, 1, &file_path.display().to_string(), &file_body_text(&body, args.line_numbers))?,
+            OutputFormat::Jsonl => {
+                let name = file_path.file_name().unwrap_or_default().to_string_lossy();
+                write_jsonl_record(file_path, &name, body, Fit::Full, output_file, skipped)?;
+            },
+            OutputFormat::Json => unreachable!("JSON output is written as a whole by write_json_document"),
+        }
+    }
+    Ok(())
+}
+
+/// Writes the "Skipped / Errors" section listing every path that was left out
+/// or only partially documented.
+///
+/// `document_index` is the first unused index for the XML format, where the
+/// list is written as one more document.
+pub fn write_skipped<W: Write>(skipped: &SkipLog, output_file: &mut W, args: &Args, document_index: usize) -> io::Result<()> {
+    let rows = skipped.entries().iter().map(|entry| {
+        let path = entry.path.strip_prefix(&args.repo_path).unwrap_or(&entry.path);
+        (path.display().to_string(), entry.reason.to_string(), entry.message.as_str())
+    });
+
+    match args.output_format {
+        OutputFormat::Text => {
+            writeln!(output_file, "Skipped / Errors Begins -->\n")?;
+            for (path, reason, message) in rows {
+                writeln!(output_file, "{} ({}): {}", path, reason, message)?;
+            }
+            writeln!(output_file, "\n<-- Skipped / Errors Ends\n")?;
+        },
+        OutputFormat::Markdown => {
+            writeln!(output_file, "## Skipped / Errors\n")?;
+            for (path, reason, message) in rows {
+                writeln!(output_file, "- `{}` ({}): {}", path, reason, message)?;
+            }
+            writeln!(output_file)?;
+        },
+        OutputFormat::HTML => {
+            writeln!(output_file, "    <h2>Skipped / Errors</h2>")?;
+            writeln!(output_file, "    <ul>")?;
+            for (path, reason, message) in rows {
+                writeln!(output_file, "        <li><code>{}</code> ({}): {}</li>", escape_html(&path), reason, escape_html(message))?;
+            }
+            writeln!(output_file, "    </ul>")?;
+        },
+        OutputFormat::Xml => {
+            let list: String = rows
+                .map(|(path, reason, message)| format!("{} ({}): {}\n", path, reason, message))
+                .collect();
+            write_xml_document(output_file, document_index, "Skipped / Errors", &list)?;
+        },
+        OutputFormat::Jsonl => {
+            for record in skipped_records(skipped, &args.repo_path) {
+                write_jsonl_line(&JsonlRecord::Skipped(record), output_file)?;
+            }
+        },
+        OutputFormat::Json => unreachable!("JSON output is written as a whole by write_json_document"),
+    }
+    Ok(())
+}
+
+/// Writes the trailer listing files that were shortened or dropped to stay
+/// under `--max-tokens`.
+///
+/// `document_index` is the first unused index for the XML format.
+pub fn write_budget_trailer<W: Write>(plan: &BudgetPlan, output_file: &mut W, args: &Args, document_index: usize) -> io::Result<()> {
+    let rows = plan.trailer_rows(&args.repo_path);
+    let intro = format!("These files were shortened or left out to stay under {} tokens:", format_count(plan.max_tokens()));
+
+    match args.output_format {
+        OutputFormat::Text => {
+            writeln!(output_file, "Token Budget Begins -->\n")?;
+            writeln!(output_file, "{}", intro)?;
+            for row in rows {
+                writeln!(output_file, "{}", row)?;
+            }
+            writeln!(output_file, "\n<-- Token Budget Ends\n")?;
+        },
+        OutputFormat::Markdown => {
+            writeln!(output_file, "## Token Budget\n")?;
+            writeln!(output_file, "{}\n", intro)?;
+            for row in rows {
+                writeln!(output_file, "- {}", row)?;
+            }
+            writeln!(output_file)?;
+        },
+        OutputFormat::HTML => {
+            writeln!(output_file, "    <h2>Token Budget</h2>")?;
+            writeln!(output_file, "    <p>{}</p>", escape_html(&intro))?;
+            writeln!(output_file, "    <ul>")?;
+            for row in rows {
+                writeln!(output_file, "        <li>{}</li>", escape_html(&row))?;
+            }
+            writeln!(output_file, "    </ul>")?;
+        },
+        OutputFormat::Xml => {
+            let list: String = rows.iter().map(|row| format!("{}\n", row)).collect();
+            write_xml_document(output_file, document_index, "Token Budget", &format!("{}\n{}", intro, list))?;
+        },
+        OutputFormat::Jsonl => {
+            write_jsonl_line(&JsonlRecord::Budget(budget_record(plan, &args.repo_path)), output_file)?;
+        },
+        OutputFormat::Json => unreachable!("JSON output is written as a whole by write_json_document"),
+    }
+    Ok(())
+}
+
+/// Writes the statistics section: file counts and sizes, line counts per
+/// language, and the largest files and deepest paths.
+///
+/// `document_index` is the index of the statistics document in the XML format.
+pub fn write_stats_section<W: Write>(stats: &RepoStats, output_file: &mut W, args: &Args, document_index: usize) -> io::Result<()> {
+    let summary = format!("{} files ({} binary), {} in total", format_count(stats.files), format_count(stats.binary_files), format_size(stats.total_size));
+    let mut languages = stats.languages.clone();
+    languages.push(stats.total_lines());
 
-        writeln!(output_file, "[File Begins] {}", relative_path.display())?;
-        write_file_content(path, output_file)?;
-        writeln!(output_file, "[File Ends] {}", relative_path.display())?;
+    match args.output_format {
+        OutputFormat::Text => {
+            writeln!(output_file, "\nStatistics Begins -->\n")?;
+            write!(output_file, "{}", stats_text(stats, &summary, &languages))?;
+            writeln!(output_file, "\n<-- Statistics Ends")?;
+        },
+        OutputFormat::Markdown => {
+            writeln!(output_file, "## Statistics\n")?;
+            writeln!(output_file, "{}\n", summary)?;
+            writeln!(output_file, "| Language | Files | Code | Comments | Blank |")?;
+            writeln!(output_file, "|---|--:|--:|--:|--:|")?;
+            for language in &languages {
+                writeln!(
+                    output_file, "| {} | {} | {} | {} | {} |",
+                    language.language, format_count(language.files), format_count(language.code), format_count(language.comments), format_count(language.blank),
+                )?;
+            }
+            writeln!(output_file, "\nLargest files:\n")?;
+            for file in &stats.largest_files {
+                writeln!(output_file, "- `{}` ({})", file.path, format_size(file.size))?;
+            }
+            writeln!(output_file, "\nDeepest paths:\n")?;
+            for path in &stats.deepest_paths {
+                writeln!(output_file, "- `{}` (depth {})", path.path, path.depth)?;
+            }
+            writeln!(output_file)?;
+        },
+        OutputFormat::HTML => {
+            writeln!(output_file, "    <h2>Statistics</h2>")?;
+            writeln!(output_file, "    <p>{}</p>", escape_html(&summary))?;
+            writeln!(output_file, "    <table class=\"stats\">")?;
+            writeln!(output_file, "        <tr><th>Language</th><th>Files</th><th>Code</th><th>Comments</th><th>Blank</th></tr>")?;
+            for language in &languages {
+                writeln!(
+                    output_file, "        <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    escape_html(&language.language), format_count(language.files), format_count(language.code), format_count(language.comments), format_count(language.blank),
+                )?;
+            }
+            writeln!(output_file, "    </table>")?;
+            writeln!(output_file, "    <h3>Largest files</h3>")?;
+            writeln!(output_file, "    <ul>")?;
+            for file in &stats.largest_files {
+                writeln!(output_file, "        <li><code>{}</code> ({})</li>", escape_html(&file.path), format_size(file.size))?;
+            }
+            writeln!(output_file, "    </ul>")?;
+            writeln!(output_file, "    <h3>Deepest paths</h3>")?;
+            writeln!(output_file, "    <ul>")?;
+            for path in &stats.deepest_paths {
+                writeln!(output_file, "        <li><code>{}</code> (depth {})</li>", escape_html(&path.path), path.depth)?;
+            }
+            writeln!(output_file, "    </ul>")?;
+        },
+        OutputFormat::Xml => {
+            write_xml_document(output_file, document_index, "Statistics", &stats_text(stats, &summary, &languages))?;
+        },
+        OutputFormat::Jsonl => {
+            write_jsonl_line(&JsonlRecord::Stats(stats.clone()), output_file)?;
+        },
+        OutputFormat::Json => unreachable!("JSON output is written as a whole by write_json_document"),
     }
     Ok(())
 }
 
-pub fn write_file_content(file_path: &Path, output_file: &mut File) -> io::Result<()> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
+/// Plain text rendering of the statistics, with the language rows in aligned
+/// columns.
+fn stats_text(stats: &RepoStats, summary: &str, languages: &[LanguageStats]) -> String {
+    let width = languages.iter().map(|l| l.language.len()).max().unwrap_or(0).max("Language".len());
+    let mut text = format!("{}\n\n", summary);
+    text.push_str(&format!("{:<width$}  {:>8}  {:>10}  {:>10}  {:>10}\n", "Language", "Files", "Code", "Comments", "Blank"));
+    for language in languages {
+        text.push_str(&format!(
+            "{:<width$}  {:>8}  {:>10}  {:>10}  {:>10}\n",
+            language.language, format_count(language.files), format_count(language.code), format_count(language.comments), format_count(language.blank),
+        ));
+    }
+    text.push_str("\nLargest files:\n");
+    for file in &stats.largest_files {
+        text.push_str(&format!("  {} ({})\n", file.path, format_size(file.size)));
+    }
+    text.push_str("\nDeepest paths:\n");
+    for path in &stats.deepest_paths {
+        text.push_str(&format!("  {} (depth {})\n", path.path, path.depth));
+    }
+    text
+}
 
-    for line in reader.lines() {
-        writeln!(output_file, "{}", line?)?;
+/// Writes the index of a split document: for each part, its file name and
+/// the repository-relative paths of the files it contains.
+///
+/// `document_index` is the index of the index document in the XML format.
+pub fn write_parts_index<W: Write>(parts: &[(String, Vec<String>)], output_file: &mut W, args: &Args, document_index: usize) -> io::Result<()> {
+    match args.output_format {
+        OutputFormat::Text => {
+            writeln!(output_file, "\nParts Index Begins -->\n")?;
+            for (number, (part_file, paths)) in parts.iter().enumerate() {
+                writeln!(output_file, "Part {} ({}):", number + 1, part_file)?;
+                for path in paths {
+                    writeln!(output_file, "  {}", path)?;
+                }
+            }
+            writeln!(output_file, "\n<-- Parts Index Ends")?;
+        },
+        OutputFormat::Markdown => {
+            writeln!(output_file, "## Parts Index\n")?;
+            for (number, (part_file, paths)) in parts.iter().enumerate() {
+                writeln!(output_file, "### Part {}: `{}`\n", number + 1, part_file)?;
+                for path in paths {
+                    writeln!(output_file, "- `{}`", path)?;
+                }
+                writeln!(output_file)?;
+            }
+        },
+        OutputFormat::HTML => {
+            writeln!(output_file, "    <h2>Parts Index</h2>")?;
+            for (number, (part_file, paths)) in parts.iter().enumerate() {
+                writeln!(output_file, "    <h3>Part {}: <a href=\"{}\">{}</a></h3>", number + 1, escape_html(part_file), escape_html(part_file))?;
+                writeln!(output_file, "    <ul>")?;
+                for path in paths {
+                    let href = format!("{}#{}", part_file, html_anchor(Path::new(path)));
+                    writeln!(output_file, "        <li><a href=\"{}\">{}</a></li>", escape_html(&href), escape_html(path))?;
+                }
+                writeln!(output_file, "    </ul>")?;
+            }
+        },
+        OutputFormat::Xml => {
+            let index: String = parts.iter().enumerate()
+                .map(|(number, (part_file, paths))| {
+                    let files: String = paths.iter().map(|path| format!("  {}\n", path)).collect();
+                    format!("Part {} ({}):\n{}", number + 1, part_file, files)
+                })
+                .collect();
+            write_xml_document(output_file, document_index, "Parts Index", &index)?;
+        },
+        OutputFormat::Json | OutputFormat::Jsonl => unreachable!("JSON output is never split into parts"),
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+fn file_body_text(body: &FileBody, line_numbers: bool) -> String {
+    match body {
+        FileBody::Binary(info) => info.to_string(),
+        FileBody::Text(text) if !line_numbers => text.clone(),
+        _ => body_lines(body, line_numbers).iter().map(|line| format!("{}\n", line)).collect(),
+    }
+}
+
+/// Writes one `<document>` element of the XML format. The content goes into a
+/// CDATA section, split wherever it contains `]]>`.
+pub fn write_xml_document<W: Write>(output_file: &mut W, index: usize, source: &str, content: &str) -> io::Result<()> {
+    writeln!(output_file, "<document index=\"{}\">", index)?;
+    writeln!(output_file, "<source>{}</source>", escape_html(source))?;
+    writeln!(output_file, "<document_content><![CDATA[{}]]></document_content>", xml_cdata_text(content))?;
+    writeln!(output_file, "</document>")
+}
+
+/// Makes text safe inside a CDATA section: closes and reopens the section
+/// around `]]>` and replaces characters XML 1.0 does not allow.
+fn xml_cdata_text(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\t' | '\n' | '\r' => c,
+            c if c < ' ' || c == '\u{fffe}' || c == '\u{ffff}' => '\u{fffd}',
+            c => c,
+        })
+        .collect::<String>()
+        .replace("]]>", "]]]]><![CDATA[>")
+}
+
+/// Writes one file as a single line of JSON. Returns false, after recording
+/// why, if the file could not be hashed.
+fn write_jsonl_record<W: Write>(file_path: &Path, relative_path: &str, body: FileBody, fit: Fit, output_file: &mut W, skipped: &mut SkipLog) -> io::Result<bool> {
+    let record = match file_record_from_body(file_path, relative_path, body, fit) {
+        Ok(record) => record,
+        Err(e) => {
+            skipped.record_io(file_path, &e);
+            return Ok(false);
+        }
+    };
+    write_jsonl_line(&JsonlRecord::File(record), output_file)?;
+    Ok(true)
+}
+
+fn write_jsonl_line<W: Write>(record: &JsonlRecord, output_file: &mut W) -> io::Result<()> {
+    serde_json::to_writer(&mut *output_file, record)?;
+    writeln!(output_file)
+}
+
+/// Serializes a whole document as pretty-printed JSON.
+pub fn write_json_document<W: Write>(document: &Document, output_file: &mut W) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *output_file, document)?;
+    writeln!(output_file)
+}
+
+/// Escapes text for use in HTML or XML element content and attribute values.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
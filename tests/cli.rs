@@ -378,4 +378,1594 @@ fn test_follow_symlinks() {
 
     let content = fs::read_to_string(&output_file).unwrap();
     assert!(content.contains("target.txt"));
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_use_gitignore() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_gitignore");
+    fs::create_dir(&test_dir).unwrap();
+
+    fs::write(test_dir.join(".gitignore"), "*.log\n!important.log\nartifacts/\n/anchored.md\n").unwrap();
+    fs::write(test_dir.join("main.rs"), "fn main() {}").unwrap();
+    fs::write(test_dir.join("debug.log"), "noise").unwrap();
+    fs::write(test_dir.join("important.log"), "keep me").unwrap();
+    fs::write(test_dir.join("anchored.md"), "root only").unwrap();
+    fs::write(test_dir.join("artifacts"), "a file, not a directory").unwrap();
+
+    let nested_dir = test_dir.join("nested");
+    fs::create_dir(&nested_dir).unwrap();
+    fs::write(nested_dir.join(".gitignore"), "local.txt\n").unwrap();
+    fs::write(nested_dir.join("local.txt"), "ignored below nested").unwrap();
+    fs::write(nested_dir.join("anchored.md"), "nested copy").unwrap();
+    fs::create_dir(nested_dir.join("artifacts")).unwrap();
+    fs::write(nested_dir.join("artifacts").join("blob.rs"), "generated").unwrap();
+    fs::write(test_dir.join("local.txt"), "kept at the root").unwrap();
+
+    let output_file = temp_dir.path().join("output.txt");
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    println!("Output file content:\n{}", content);
+
+    assert!(content.contains("main.rs"));
+    assert!(!content.contains("debug.log"), "Ignored by *.log");
+    assert!(content.contains("important.log"), "Re-included by negation");
+    assert!(!content.contains("root only"), "Anchored pattern applies at the root");
+    assert!(content.contains("nested copy"), "Anchored pattern does not apply below the root");
+    assert!(content.contains("a file, not a directory"), "Directory-only pattern does not match files");
+    assert!(!content.contains("blob.rs"), "Directory-only pattern matches nested directories");
+    assert!(!content.contains("ignored below nested"), "Nested .gitignore applies to its directory");
+    assert!(content.contains("kept at the root"), "Nested .gitignore does not apply to parents");
+}
+
+#[test]
+fn test_use_gitignore_disabled() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_gitignore_disabled");
+    fs::create_dir(&test_dir).unwrap();
+    fs::write(test_dir.join(".gitignore"), "*.log\n").unwrap();
+    fs::write(test_dir.join("debug.log"), "noise").unwrap();
+
+    let output_file = temp_dir.path().join("output.txt");
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    assert!(content.contains("debug.log"));
+}
+
+#[test]
+fn test_git_info_exclude() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_info_exclude");
+    let info_dir = test_dir.join(".git").join("info");
+    fs::create_dir_all(&info_dir).unwrap();
+    fs::write(info_dir.join("exclude"), "secret.txt\n").unwrap();
+    fs::write(test_dir.join(".gitignore"), "!public.txt\n").unwrap();
+
+    let src_dir = test_dir.join("src");
+    fs::create_dir(&src_dir).unwrap();
+    fs::write(src_dir.join("secret.txt"), "password").unwrap();
+    fs::write(src_dir.join("public.txt"), "hello").unwrap();
+
+    let output_file = temp_dir.path().join("output.txt");
+
+    // Point at a subdirectory so the work tree root has to be discovered
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&src_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    assert!(!content.contains("secret.txt"));
+    assert!(content.contains("public.txt"));
+}
+
+#[test]
+fn test_hidden_excluded_by_default() {
+    let temp_dir = setup_test_dir();
+    let git_dir = temp_dir.path().join(".git");
+    fs::create_dir(&git_dir).unwrap();
+    fs::write(git_dir.join("HEAD"), "ref: refs/heads/main").unwrap();
+
+    let output_file = temp_dir.path().join("output.txt");
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(temp_dir.path())
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    assert!(content.contains("test.txt"));
+    assert!(!content.contains(".hiddenfile"));
+    assert!(!content.contains("hidden.txt"));
+    assert!(!content.contains("HEAD"));
+
+    // .git stays excluded even when hidden files are included
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(temp_dir.path())
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--include-hidden")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    assert!(content.contains("hidden.txt"));
+    assert!(!content.contains("HEAD"));
+}
+
+#[test]
+fn test_allow_hidden() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_allow_hidden");
+    let workflows_dir = test_dir.join(".github").join("workflows");
+    fs::create_dir_all(&workflows_dir).unwrap();
+    fs::write(workflows_dir.join("ci.yml"), "ci workflow").unwrap();
+    fs::write(test_dir.join(".github").join("dependabot.yml"), "dependabot").unwrap();
+    fs::write(test_dir.join(".github").join("CODEOWNERS"), "owners").unwrap();
+    fs::create_dir(test_dir.join(".cargo")).unwrap();
+    fs::write(test_dir.join(".cargo").join("config.toml"), "cargo config").unwrap();
+    fs::write(test_dir.join(".cargo").join("credentials.toml"), "token").unwrap();
+    fs::write(test_dir.join(".env"), "SECRET=1").unwrap();
+
+    let output_file = temp_dir.path().join("output.txt");
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--allow-hidden")
+        .arg(".github/workflows/**,.cargo/config.toml")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    println!("Output file content:\n{}", content);
+    assert!(content.contains("[File Begins] .github/workflows/ci.yml\nci workflow\n"), "Allowlisted settings files are kept");
+    assert!(content.contains("cargo config"));
+    assert!(!content.contains("CODEOWNERS"));
+    assert!(!content.contains("dependabot"));
+    assert!(!content.contains("credentials.toml"));
+    assert!(!content.contains(".env"));
+
+    // A bare name matches in every hidden directory, but directories without
+    // a match are left out of the tree
+    fs::create_dir_all(test_dir.join(".venv").join("lib").join("site-packages")).unwrap();
+    fs::write(test_dir.join(".venv").join("lib").join("site-packages").join("pkg.py"), "import os").unwrap();
+    fs::create_dir_all(test_dir.join(".cache").join("nested")).unwrap();
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--allow-hidden")
+        .arg(".env")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    println!("Output file content:\n{}", content);
+    assert!(content.contains("SECRET=1"));
+    assert!(!content.contains(".venv"));
+    assert!(!content.contains("site-packages"));
+    assert!(!content.contains(".cache"));
+    assert!(!content.contains(".github"));
+}
+
+#[test]
+fn test_binary_placeholder() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_binary");
+    fs::create_dir(&test_dir).unwrap();
+
+    let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR".to_vec();
+    png.resize(2048, 0);
+    fs::write(test_dir.join("logo"), &png).unwrap();
+    fs::write(test_dir.join("blob.txt"), b"abc\x00\x01\x02def").unwrap();
+    fs::write(test_dir.join("readme.txt"), "plain text").unwrap();
+    // Printable magic numbers only count alongside non-text bytes
+    fs::write(test_dir.join("notes.txt"), "RIFF notes for the project, kept as text").unwrap();
+    fs::write(test_dir.join("f.txt"), "see ftyp sth").unwrap();
+    fs::write(test_dir.join("sound.wav"), b"RIFF\x24\x08\x00\x00WAVEfmt ").unwrap();
+
+    let output_file = temp_dir.path().join("output.txt");
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    println!("Output file content:\n{}", content);
+    assert!(content.contains("logo [binary file, 2 KB, PNG image]"));
+    assert!(content.contains("RIFF notes for the project, kept as text"));
+    assert!(content.contains("see ftyp sth"));
+    assert!(content.contains("sound.wav [binary file, 16 B, WAV audio]"));
+    assert!(content.contains("[binary file, 9 B, data]"));
+    assert!(!content.contains("IHDR"));
+    assert!(content.contains("plain text"));
+}
+
+#[test]
+fn test_binary_skip() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_binary_skip");
+    fs::create_dir(&test_dir).unwrap();
+    fs::write(test_dir.join("archive"), b"PK\x03\x04\x14\x00\x00\x00").unwrap();
+    fs::write(test_dir.join("readme.txt"), "plain text").unwrap();
+
+    let output_file = temp_dir.path().join("output.txt");
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--binary-files")
+        .arg("skip")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    assert!(!content.contains("archive"));
+    assert!(content.contains("plain text"));
+}
+
+#[test]
+fn test_non_utf8_encodings() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_encodings");
+    fs::create_dir(&test_dir).unwrap();
+
+    fs::write(test_dir.join("latin1.txt"), b"caf\xe9 cr\xe8me\n").unwrap();
+    let mut utf16 = vec![0xff, 0xfe];
+    utf16.extend("utf16 h\u{e9}llo\n".encode_utf16().flat_map(|u| u.to_le_bytes()));
+    fs::write(test_dir.join("utf16.txt"), &utf16).unwrap();
+    fs::write(test_dir.join("bom.txt"), b"\xef\xbb\xbfwith bom\n").unwrap();
+    fs::write(test_dir.join("damaged.txt"), b"na\xc3\xafve \xff broken\n").unwrap();
+
+    let output_file = temp_dir.path().join("output.txt");
+
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("damaged.txt: decode error"), "Lossy decoding should be reported");
+    assert!(!stderr.contains("latin1.txt: "), "Transcoding is not a warning");
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    println!("Output file content:\n{}", content);
+    assert!(content.contains("café crème"));
+    assert!(content.contains("utf16 héllo"));
+    assert!(content.contains("\nwith bom"));
+    assert!(content.contains("naïve \u{fffd} broken"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_skipped_paths_section() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_skipped");
+    fs::create_dir(&test_dir).unwrap();
+    fs::write(test_dir.join("good.txt"), "good content").unwrap();
+    fs::write(test_dir.join("damaged.txt"), b"na\xc3\xafve \xff broken\n").unwrap();
+    std::os::unix::fs::symlink(test_dir.join("missing.txt"), test_dir.join("dangling.txt")).unwrap();
+
+    let output_file = temp_dir.path().join("output.txt");
+
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("2 path(s) were skipped or had errors"));
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    println!("Output file content:\n{}", content);
+    assert!(content.contains("good content"), "Other files are still documented");
+    assert!(content.contains("Skipped / Errors Begins"));
+    assert!(content.contains("dangling.txt (broken symlink)"));
+    assert!(content.contains("damaged.txt (decode error)"));
+
+    // Markdown gets its own section heading
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--output-format")
+        .arg("markdown")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    assert!(content.contains("## Skipped / Errors"));
+    assert!(content.contains("- `dangling.txt` (broken symlink)"));
+
+    // --strict still writes the document but exits with a distinct code
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--strict")
+        .assert()
+        .code(3);
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    assert!(content.contains("good content"));
+}
+
+#[test]
+fn test_strict_without_problems() {
+    let temp_dir = setup_test_dir();
+    let output_file = temp_dir.path().join("output.txt");
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(temp_dir.path())
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--strict")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    assert!(!content.contains("Skipped / Errors"));
+}
+
+#[test]
+fn test_tree_rendering() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_tree");
+    fs::create_dir_all(test_dir.join("b").join("inner")).unwrap();
+    fs::create_dir(test_dir.join("a")).unwrap();
+    for file in ["z.rs", "a.rs", "b/y.rs", "b/inner/x.rs", "a/q.rs"] {
+        fs::write(test_dir.join(file), file).unwrap();
+    }
+
+    let output_file = temp_dir.path().join("output.txt");
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    let expected = "\
+test_tree
+├── a
+│   └── q.rs
+├── b
+│   ├── inner
+│   │   └── x.rs
+│   └── y.rs
+├── a.rs
+└── z.rs
+";
+    assert!(content.contains(expected), "Unexpected tree:\n{}", content);
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--sort")
+        .arg("alphabetical")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    let expected = "\
+test_tree
+├── a
+│   └── q.rs
+├── a.rs
+├── b
+│   ├── inner
+│   │   └── x.rs
+│   └── y.rs
+└── z.rs
+";
+    assert!(content.contains(expected), "Unexpected tree:\n{}", content);
+
+    // File contents follow the same order as the tree
+    let a = content.find("[File Begins] a.rs").unwrap();
+    let b = content.find("[File Begins] b/y.rs").unwrap();
+    assert!(a < b);
+}
+
+#[test]
+fn test_html_output() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_html");
+    fs::create_dir_all(test_dir.join("src")).unwrap();
+    fs::write(test_dir.join("src").join("page.txt"), "<script>alert('x')</script>\n</body>\n").unwrap();
+    fs::write(test_dir.join("a & b.txt"), "ampersand").unwrap();
+
+    let output_file = temp_dir.path().join("output.html");
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--output-format")
+        .arg("html")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    println!("Output file content:\n{}", content);
+
+    assert!(!content.contains("<script>"), "File contents must be escaped");
+    assert!(content.contains("<pre><code>&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;\n&lt;/body&gt;</code></pre>"));
+    assert_eq!(content.matches("</body>").count(), 1);
+
+    // Tree entries link to per-file anchors
+    assert!(content.contains("<a href=\"#file-src/page.txt\">page.txt</a>"));
+    assert!(content.contains("<section class=\"file\" id=\"file-src/page.txt\">"));
+    assert!(content.contains("<a href=\"#file-a_20_26_20b.txt\">a &amp; b.txt</a>"));
+    assert!(content.contains("id=\"file-a_20_26_20b.txt\""));
+
+    // Section headings come right before their sections
+    let tree_heading = content.find("<h2>Directory/File Tree</h2>").unwrap();
+    let content_heading = content.find("<h2>File Content</h2>").unwrap();
+    assert!(tree_heading < content.find("<ul class=\"tree\">").unwrap());
+    assert!(content.find("<ul class=\"tree\">").unwrap() < content_heading);
+    assert!(content.trim_end().ends_with("</html>"));
+}
+
+#[test]
+fn test_markdown_output() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_markdown");
+    fs::create_dir_all(test_dir.join("src")).unwrap();
+    fs::write(test_dir.join("src").join("main.rs"), "fn main() {}\n").unwrap();
+    fs::write(test_dir.join("run"), "#!/usr/bin/env python3\nprint('hi')\n").unwrap();
+    fs::write(test_dir.join("notes.md"), "Example:\n```rust\nlet x = 1;\n```\n").unwrap();
+
+    let output_file = temp_dir.path().join("output.md");
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--output-format")
+        .arg("markdown")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    println!("Output file content:\n{}", content);
+
+    assert!(!content.contains("[File Begins]"));
+    assert!(content.contains("## Directory/File Tree\n\n```text\ntest_markdown\n├── src\n│   └── main.rs\n"));
+    assert!(content.contains("### `src/main.rs`\n\n```rust\nfn main() {}\n```\n"));
+    assert!(content.contains("### `run`\n\n```python\n"), "Language detected from the shebang");
+    assert!(content.contains("````markdown\nExample:\n```rust\nlet x = 1;\n```\n````\n"), "Fence must outgrow backticks in the content");
+
+    // Section headings appear once, right before their sections
+    assert_eq!(content.matches("## Directory/File Tree").count(), 1);
+    assert!(content.find("## Directory/File Tree").unwrap() < content.find("## File Content").unwrap());
+}
+
+#[test]
+fn test_json_output() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_json");
+    fs::create_dir_all(test_dir.join("src")).unwrap();
+    fs::write(test_dir.join("src").join("main.rs"), "fn main() {\n    println!(\"[File Ends]\");\n}\n").unwrap();
+    fs::write(test_dir.join("logo"), b"\x89PNG\r\n\x1a\n\x00\x00").unwrap();
+
+    let output_file = temp_dir.path().join("output.json");
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--output-format")
+        .arg("json")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    let document: serde_json::Value = serde_json::from_str(&content).expect("Output should be valid JSON");
+
+    assert_eq!(document["schema_version"], 1);
+    assert_eq!(document["repository"]["name"], "test_json");
+    assert_eq!(document["repository"]["file_count"], 2);
+
+    let tree = &document["tree"];
+    assert_eq!(tree["type"], "directory");
+    assert_eq!(tree["children"][0]["name"], "src");
+    assert_eq!(tree["children"][0]["children"][0]["path"], "src/main.rs");
+    assert_eq!(tree["children"][0]["children"][0]["type"], "file");
+
+    let files = document["files"].as_array().unwrap();
+    let main_rs = files.iter().find(|f| f["path"] == "src/main.rs").unwrap();
+    assert_eq!(main_rs["language"], "rust");
+    assert_eq!(main_rs["lines"], 3);
+    assert_eq!(main_rs["size"], 43);
+    assert_eq!(main_rs["content"], "fn main() {\n    println!(\"[File Ends]\");\n}\n");
+    assert_eq!(main_rs["sha256"].as_str().unwrap().len(), 64);
+
+    let logo = files.iter().find(|f| f["path"] == "logo").unwrap();
+    assert_eq!(logo["binary"], "PNG image");
+    assert!(logo["content"].is_null());
+
+    assert!(document["skipped"].as_array().unwrap().is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_xml_output() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_xml");
+    fs::create_dir_all(test_dir.join("src")).unwrap();
+    fs::write(test_dir.join("src").join("lib.rs"), "// <documents> & ]]> inside\n").unwrap();
+    fs::write(test_dir.join("a<b>.txt"), "angle brackets").unwrap();
+    std::os::unix::fs::symlink(test_dir.join("missing"), test_dir.join("dangling")).unwrap();
+
+    let output_file = temp_dir.path().join("output.xml");
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--output-format")
+        .arg("xml")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    println!("Output file content:\n{}", content);
+
+    assert!(content.starts_with("<documents>\n<document index=\"1\">\n<source>Directory/File Tree</source>\n<document_content><![CDATA[test_xml\n"));
+    assert!(content.contains("<document index=\"2\">\n<source>src/lib.rs</source>\n<document_content><![CDATA[// <documents> & ]]]]><![CDATA[> inside\n]]></document_content>\n</document>"));
+    assert!(content.contains("<document index=\"3\">\n<source>a&lt;b&gt;.txt</source>"));
+    assert!(content.contains("<document index=\"4\">\n<source>Skipped / Errors</source>"));
+    assert!(content.trim_end().ends_with("</documents>"));
+}
+
+#[test]
+fn test_jsonl_output() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_jsonl");
+    fs::create_dir_all(test_dir.join("src")).unwrap();
+    fs::write(test_dir.join("src").join("lib.rs"), "pub fn a() {}\n").unwrap();
+    fs::write(test_dir.join("notes.txt"), "line one\nline two\n").unwrap();
+
+    let output_file = temp_dir.path().join("output.jsonl");
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--output-format")
+        .arg("jsonl")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    let records: Vec<serde_json::Value> = content.lines()
+        .map(|line| serde_json::from_str(line).expect("Every line should be a JSON object"))
+        .collect();
+
+    assert_eq!(records.len(), 2, "One record per file and nothing else");
+    assert_eq!(records[0]["type"], "file");
+    assert_eq!(records[0]["path"], "src/lib.rs");
+    assert_eq!(records[0]["language"], "rust");
+    assert_eq!(records[0]["size"], 14);
+    assert_eq!(records[0]["content"], "pub fn a() {}\n");
+    assert_eq!(records[0]["sha256"].as_str().unwrap().len(), 64);
+    assert_eq!(records[1]["path"], "notes.txt");
+    assert_eq!(records[1]["lines"], 2);
+
+    // The budget and skipped paths follow the files as records of their own
+    fs::write(test_dir.join("big.txt"), "hello world ".repeat(2000)).unwrap();
+    fs::write(test_dir.join("damaged.txt"), b"na\xc3\xafve \xff broken\n").unwrap();
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--output-format")
+        .arg("jsonl")
+        .arg("--max-tokens")
+        .arg("1000")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    let records: Vec<serde_json::Value> = content.lines()
+        .map(|line| serde_json::from_str(line).expect("Every line should be a JSON object"))
+        .collect();
+
+    let files: Vec<&serde_json::Value> = records.iter().filter(|r| r["type"] == "file").collect();
+    assert_eq!(files.len(), 3, "big.txt is dropped");
+    let budget = &records[records.len() - 2];
+    assert_eq!(budget["type"], "budget");
+    assert_eq!(budget["max_tokens"], 1000);
+    assert_eq!(budget["reduced"][0]["path"], "big.txt");
+    assert_eq!(budget["reduced"][0]["action"], "dropped");
+    let skipped = &records[records.len() - 1];
+    assert_eq!(skipped["type"], "skipped");
+    assert_eq!(skipped["path"], "damaged.txt");
+    assert_eq!(skipped["reason"], "decode error");
+}
+
+#[test]
+fn test_token_counts() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_tokens");
+    fs::create_dir_all(test_dir.join("src")).unwrap();
+    fs::write(test_dir.join("src").join("big.txt"), "hello world ".repeat(100)).unwrap();
+    fs::write(test_dir.join("small.txt"), "hello world").unwrap();
+
+    let output_file = temp_dir.path().join("output.txt");
+
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--count-tokens")
+        .arg("--tree-tokens")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    println!("Command stderr: {}", stderr);
+    assert!(stderr.contains("Total tokens (o200k_base): "));
+    let big = stderr.find("201  src/big.txt").expect("Per-file count for big.txt");
+    let small = stderr.find("2  small.txt").expect("Per-file count for small.txt");
+    assert!(big < small, "Largest files are listed first");
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    println!("Output file content:\n{}", content);
+    assert!(content.contains("test_tokens (203 tokens)"));
+    assert!(content.contains("├── src (201 tokens)"));
+    assert!(content.contains("└── small.txt (2 tokens)"));
+
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--count-tokens=false")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("Total tokens"));
+    assert!(!fs::read_to_string(&output_file).unwrap().contains("tokens)"));
+
+    // Counting is opt-in; --tree-tokens alone annotates the tree without a summary
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--tree-tokens")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("Total tokens"));
+    assert!(fs::read_to_string(&output_file).unwrap().contains("└── small.txt (2 tokens)"));
+}
+
+#[test]
+fn test_max_tokens() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_budget");
+    fs::create_dir_all(test_dir.join("src")).unwrap();
+    fs::write(test_dir.join("README.md"), "# Demo\nA small readme.\n").unwrap();
+    let main_rs: String = (0..100).map(|i| format!("fn f{}() {{\n    let x = {};\n}}\n", i, i)).collect();
+    fs::write(test_dir.join("src").join("main.rs"), main_rs).unwrap();
+    fs::write(test_dir.join("notes.txt"), "alpha beta gamma delta epsilon\n".repeat(400)).unwrap();
+
+    let output_file = temp_dir.path().join("output.txt");
+
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--max-tokens")
+        .arg("1500")
+        .arg("--count-tokens")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    println!("Command stderr: {}", stderr);
+    let total: usize = stderr.lines()
+        .find_map(|line| line.strip_prefix("Total tokens (o200k_base): "))
+        .expect("Total token count")
+        .replace(',', "")
+        .parse()
+        .unwrap();
+    assert!(total <= 1500, "Document stays under the budget");
+    assert!(stderr.contains("Token budget of 1,500: 1 file(s) truncated, 1 outlined, 0 dropped"));
+
+    let content = fs::read_to_string(&output_file).unwrap();
+    println!("Output file content:\n{}", content);
+    assert!(content.contains("# Demo\nA small readme.\n"), "README is kept in full");
+    assert!(content.contains("fn f99() {\n"));
+    assert!(!content.contains("let x = 0;"), "main.rs is outlined");
+    assert!(content.contains("[outline to fit the token budget: 100 of 300 lines shown]"));
+    assert!(content.contains("[truncated to fit the token budget: first "));
+    assert!(content.contains("Token Budget Begins -->"));
+    assert!(content.contains("notes.txt (truncated, "));
+    assert!(content.contains("src/main.rs (outlined, "));
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_file)
+        .arg("--use-gitignore=false")
+        .arg("--output-format")
+        .arg("json")
+        .arg("--max-tokens")
+        .arg("800")
+        .assert()
+        .success();
+
+    let document: serde_json::Value = serde_json::from_str(&fs::read_to_string(&output_file).unwrap()).unwrap();
+    assert_eq!(document["budget"]["max_tokens"], 800);
+    let reduced = document["budget"]["reduced"].as_array().unwrap();
+    assert!(reduced.iter().any(|r| r["path"] == "notes.txt" && r["action"] == "dropped"));
+    let files = document["files"].as_array().unwrap();
+    assert!(files.iter().all(|f| f["path"] != "notes.txt"));
+    assert!(files.iter().any(|f| f["path"] == "README.md" && f.get("budget").is_none()));
+}
+
+#[test]
+fn test_split_output() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_split");
+    fs::create_dir_all(&test_dir).unwrap();
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        fs::write(test_dir.join(name), format!("{}\n", name).repeat(100)).unwrap();
+    }
+
+    // Parts are written next to the output file, here inside the repository
+    let output_file = test_dir.join("output.txt");
+    for _ in 0..2 {
+        let output = Command::cargo_bin("repo2txt")
+            .unwrap()
+            .arg("--repo-path")
+            .arg(&test_dir)
+            .arg("--output-file")
+            .arg(&output_file)
+            .arg("--use-gitignore=false")
+            .arg("--split-bytes")
+            .arg("1500")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Output written to 3 parts:"));
+    }
+
+    assert!(!output_file.exists());
+    let parts: Vec<String> = (1..=3)
+        .map(|n| fs::read_to_string(test_dir.join(format!("output.part{}.txt", n))).unwrap())
+        .collect();
+    for (n, part) in parts.iter().enumerate() {
+        println!("Part {}:\n{}", n + 1, part);
+        assert!(part.starts_with(&format!("Repository Documentation (part {} of 3)\n", n + 1)));
+        assert!(!part.contains("[File Begins] output.part"), "Earlier parts are not documented");
+    }
+
+    assert!(parts[0].contains("Directory/File Tree Begins -->"));
+    assert!(parts[0].contains("Part 1 (output.part1.txt):\n  a.txt\nPart 2 (output.part2.txt):\n  b.txt\nPart 3 (output.part3.txt):\n  c.txt\n"));
+    assert!(parts[0].contains("[File Begins] a.txt"));
+    assert!(!parts[1].contains("Directory/File Tree Begins -->"));
+    assert!(parts[1].contains("[File Begins] b.txt"));
+    assert!(parts[2].contains("[File Begins] c.txt"));
+    assert_eq!(parts.iter().map(|p| p.matches("[File Begins] ").count()).sum::<usize>(), 3);
+}
+
+#[test]
+fn test_split_parts_stay_under_limit() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_split_limit");
+    fs::create_dir_all(&test_dir).unwrap();
+    // Many small files, so the parts are filled close to the limit
+    for n in 0..150 {
+        fs::write(test_dir.join(format!("f{:03}.txt", n)), "content line\n".repeat(8)).unwrap();
+    }
+    // Listed in the skipped trailer of the last part
+    for n in 0..10 {
+        fs::write(test_dir.join(format!("damaged_{}.txt", n)), b"na\xc3\xafve \xff broken\n").unwrap();
+    }
+
+    let output_dir = temp_dir.path().join("out");
+    fs::create_dir_all(&output_dir).unwrap();
+    // HTML parts carry a style sheet and a linked tree, so they need more room
+    for (format, limit) in [("text", 6000), ("markdown", 6000), ("xml", 6000), ("html", 26000)] {
+        for entry in fs::read_dir(&output_dir).unwrap() {
+            fs::remove_file(entry.unwrap().path()).unwrap();
+        }
+        Command::cargo_bin("repo2txt")
+            .unwrap()
+            .arg("--repo-path")
+            .arg(&test_dir)
+            .arg("--output-file")
+            .arg(output_dir.join("output.txt"))
+            .arg("--use-gitignore=false")
+            .arg("--output-format")
+            .arg(format)
+            .arg("--split-bytes")
+            .arg(limit.to_string())
+            .assert()
+            .success();
+
+        let parts: Vec<_> = fs::read_dir(&output_dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+        assert!(parts.len() > 2, "{}: expected several parts, got {}", format, parts.len());
+        for part in &parts {
+            let size = fs::metadata(part).unwrap().len();
+            assert!(size <= limit, "{}: {} has {} bytes, over the limit of {}", format, part.display(), size, limit);
+        }
+    }
+}
+
+#[test]
+fn test_stdout_output() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_stdout");
+    fs::create_dir_all(&test_dir).unwrap();
+    fs::write(test_dir.join("a.txt"), "alpha\n").unwrap();
+
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg("-")
+        .arg("--use-gitignore=false")
+        .arg("--count-tokens")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    println!("Command stdout: {}\nCommand stderr: {}", stdout, stderr);
+    assert!(stdout.starts_with("Repository Documentation\n"));
+    assert!(stdout.contains("[File Begins] a.txt\nalpha\n[File Ends] a.txt\n"));
+    assert!(!stdout.contains("Debug:"));
+    assert!(!stdout.contains("Total tokens"));
+    assert!(stderr.contains("Output written to stdout"));
+    assert!(stderr.contains("Total tokens (o200k_base): "));
+    assert!(!stderr.contains("No --output-file given"), "An explicit - needs no notice");
+    assert!(!test_dir.join("-").exists());
+
+    // Without --output-file, a non-terminal stdout gets the document
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .current_dir(temp_dir.path())
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--use-gitignore=false")
+        .arg("--output-format")
+        .arg("jsonl")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let record: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(record["path"], "a.txt");
+    assert!(!temp_dir.path().join("output.txt").exists());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No --output-file given and stdout is not a terminal; writing the document to stdout"));
+
+    // The notice is left out with --quiet
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .current_dir(temp_dir.path())
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--use-gitignore=false")
+        .arg("-q")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+
+    // --explain and --dry-run print their report on stdout and write no document
+    for flag in ["--explain", "--dry-run"] {
+        let output = Command::cargo_bin("repo2txt")
+            .unwrap()
+            .current_dir(temp_dir.path())
+            .arg("--repo-path")
+            .arg(&test_dir)
+            .arg("--use-gitignore=false")
+            .arg(flag)
+            .output()
+            .unwrap();
+
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("a.txt"));
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("No --output-file given"), "{} needs no notice", flag);
+    }
+}
+
+#[test]
+fn test_verbosity_levels() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_verbosity");
+    fs::create_dir_all(&test_dir).unwrap();
+    fs::write(test_dir.join("a.txt"), "alpha\n").unwrap();
+    let output_path = temp_dir.path().join("output.txt");
+
+    let run = |flags: &[&str]| {
+        let output = Command::cargo_bin("repo2txt")
+            .unwrap()
+            .arg("--repo-path")
+            .arg(&test_dir)
+            .arg("--output-file")
+            .arg(&output_path)
+            .arg("--use-gitignore=false")
+            .args(flags)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        println!("Command stderr ({:?}): {}", flags, stderr);
+        stderr
+    };
+
+    // The default level prints the summary but no debug details
+    let stderr = run(&["--count-tokens"]);
+    assert!(stderr.contains("Documentation generated successfully"));
+    assert!(stderr.contains("Total tokens (o200k_base): "));
+    assert!(!stderr.contains("Debug:"));
+    assert!(!stderr.contains("Found 1 files to document"));
+
+    let stderr = run(&["-v"]);
+    assert!(stderr.contains("Found 1 files to document"));
+    assert!(!stderr.contains("Debug:"));
+
+    let stderr = run(&["-vv"]);
+    assert!(stderr.contains("Debug: args = "));
+    assert!(stderr.contains("Debug: config loaded"));
+
+    // Quiet runs only report problems
+    let stderr = run(&["--quiet"]);
+    assert!(stderr.is_empty());
+    fs::write(test_dir.join("damaged.txt"), b"na\xc3\xafve \xff broken\n").unwrap();
+    let stderr = run(&["-q"]);
+    assert!(!stderr.contains("Documentation generated successfully"));
+    assert!(stderr.contains("damaged.txt: decode error"));
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("-q")
+        .arg("-v")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_explain() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_explain");
+    fs::create_dir_all(test_dir.join("src")).unwrap();
+    fs::create_dir_all(test_dir.join("build")).unwrap();
+    fs::write(test_dir.join(".gitignore"), "# build logs\n*.log\n").unwrap();
+    fs::write(test_dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+    fs::write(test_dir.join("build/out.js"), "built\n").unwrap();
+    fs::write(test_dir.join("debug.log"), "log\n").unwrap();
+    fs::write(test_dir.join("notes.bak"), "backup\n").unwrap();
+    fs::write(test_dir.join("settings.yaml"), "a: 1\n").unwrap();
+    let output_path = test_dir.join("output.txt");
+    fs::write(&output_path, "previous run\n").unwrap();
+
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_path)
+        .arg("--ignore-files")
+        .arg("*.bak")
+        .arg("--explain")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    println!("Command stdout: {}", stdout);
+    assert!(stdout.contains("src/main.rs: included\n"));
+    assert!(stdout.contains("build/: excluded: --exclude-dir \"build\"\n"));
+    assert!(!stdout.contains("build/out.js"), "Excluded directories are reported once");
+    assert!(stdout.contains("debug.log: excluded: .gitignore:2: *.log\n"));
+    assert!(stdout.contains("notes.bak: excluded: --ignore-files pattern \"*.bak\"\n"));
+    assert!(stdout.contains("settings.yaml: excluded: settings file"));
+    assert!(stdout.contains(".gitignore: excluded: hidden"));
+    assert!(stdout.contains("output.txt: excluded: the output file itself\n"));
+    assert_eq!(fs::read_to_string(&output_path).unwrap(), "previous run\n", "Explaining does not write the document");
+
+    // A single path inside an excluded directory names the directory's rule
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--explain")
+        .arg("build/out.js")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "build/out.js: excluded: parent build/ is excluded: --exclude-dir \"build\"\n");
+
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--max-depth")
+        .arg("1")
+        .arg("--explain")
+        .arg("src/main.rs")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "src/main.rs: excluded: deeper than --max-depth 1\n");
+
+    Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--explain")
+        .arg("missing.txt")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_dry_run() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_dry_run");
+    fs::create_dir_all(test_dir.join("src")).unwrap();
+    fs::create_dir_all(test_dir.join("target")).unwrap();
+    fs::write(test_dir.join("small.txt"), "one\n").unwrap();
+    fs::write(test_dir.join("src/big.rs"), "fn main() {\n    println!(\"hello\");\n}\n").unwrap();
+    fs::write(test_dir.join("target/skipped.txt"), "excluded\n").unwrap();
+    let output_path = temp_dir.path().join("output.txt");
+
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_path)
+        .arg("--use-gitignore=false")
+        .arg("--dry-run")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    println!("Command stdout: {}", stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].ends_with("PATH"));
+    assert!(lines[1].ends_with("  src/big.rs"), "Largest files come first");
+    assert!(lines[1].contains(" 37 B "));
+    assert!(lines[2].ends_with("  small.txt"));
+    assert!(lines[3].starts_with("Total: 2 files, 41 B, 4 lines, "));
+    assert!(lines[3].ends_with(" tokens (o200k_base)"));
+    assert!(!stdout.contains("skipped.txt"));
+    assert!(!output_path.exists(), "A dry run writes no document");
+}
+
+#[test]
+fn test_stats_section() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_stats");
+    fs::create_dir_all(test_dir.join("src/nested/deeper")).unwrap();
+    fs::write(test_dir.join("src/main.rs"), "// entry point\nfn main() {\n\n    /* two\n       lines */\n    run();\n}\n").unwrap();
+    fs::write(test_dir.join("src/nested/deeper/tool.py"), "# helper\nprint('hi')\n").unwrap();
+    fs::write(test_dir.join("notes.txt"), "plain\n").unwrap();
+    let output_path = temp_dir.path().join("output.txt");
+
+    let run = |format: &str| {
+        let output = Command::cargo_bin("repo2txt")
+            .unwrap()
+            .arg("--repo-path")
+            .arg(&test_dir)
+            .arg("--output-file")
+            .arg(&output_path)
+            .arg("--output-format")
+            .arg(format)
+            .arg("--use-gitignore=false")
+            .arg("--stats")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        fs::read_to_string(&output_path).unwrap()
+    };
+
+    let content = run("text");
+    println!("Output content: {}", content);
+    let stats_start = content.find("Statistics Begins -->").expect("Statistics section is written");
+    assert!(stats_start > content.find("<-- Directory/File Tree Ends").unwrap());
+    assert!(stats_start < content.find("File Content Begins -->").unwrap());
+    assert!(content.contains("3 files (0 binary), "));
+    let rust_row = content.lines().find(|line| line.starts_with("rust ")).unwrap();
+    assert_eq!(rust_row.split_whitespace().collect::<Vec<_>>(), ["rust", "1", "3", "3", "1"]);
+    let python_row = content.lines().find(|line| line.starts_with("python ")).unwrap();
+    assert_eq!(python_row.split_whitespace().collect::<Vec<_>>(), ["python", "1", "1", "1", "0"]);
+    assert!(content.contains("Largest files:\n  src/main.rs ("));
+    assert!(content.contains("Deepest paths:\n  src/nested/deeper/tool.py (depth 4)\n"));
+
+    // XML: the statistics are document 2 and the files follow
+    let content = run("xml");
+    assert!(content.contains("<document index=\"2\">\n<source>Statistics</source>"));
+    assert!(content.contains("<document index=\"3\">\n<source>src/nested/deeper/tool.py</source>"));
+
+    let content = run("json");
+    let document: serde_json::Value = serde_json::from_str(&content).unwrap();
+    assert_eq!(document["statistics"]["files"], 3);
+    assert_eq!(document["statistics"]["languages"][0]["language"], "rust");
+    assert_eq!(document["statistics"]["languages"][0]["comments"], 3);
+
+    // JSON Lines: a stats record comes before the file records
+    let content = run("jsonl");
+    let records: Vec<serde_json::Value> = content.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(records[0]["type"], "stats");
+    assert_eq!(records[0]["files"], 3);
+    assert_eq!(records[0]["languages"][0]["language"], "rust");
+    assert!(records[1..].iter().all(|record| record["type"] == "file"));
+
+    let content = run("markdown");
+    assert!(content.contains("## Statistics\n"));
+    assert!(content.contains("| rust | 1 | 3 | 3 | 1 |"));
+
+    let content = run("html");
+    assert!(content.contains("<tr><td>rust</td><td>1</td><td>3</td><td>3</td><td>1</td></tr>"));
+}
+
+#[test]
+fn test_line_numbers() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_line_numbers");
+    fs::create_dir_all(&test_dir).unwrap();
+    let numbered: String = (1..=12).map(|i| format!("line {}\n", i)).collect();
+    fs::write(test_dir.join("a.txt"), format!("{}\n", numbered)).unwrap();
+    let output_path = temp_dir.path().join("output.txt");
+
+    let run = |args: &[&str]| {
+        let output = Command::cargo_bin("repo2txt")
+            .unwrap()
+            .arg("--repo-path")
+            .arg(&test_dir)
+            .arg("--output-file")
+            .arg(&output_path)
+            .arg("--use-gitignore=false")
+            .arg("--line-numbers")
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        fs::read_to_string(&output_path).unwrap()
+    };
+
+    // Numbers are right-aligned to the widest one; blank lines keep theirs
+    let content = run(&[]);
+    println!("Output content: {}", content);
+    assert!(content.contains("[File Begins] a.txt\n 1 | line 1\n 2 | line 2\n"));
+    assert!(content.contains("12 | line 12\n13 |\n[File Ends] a.txt"));
+
+    let content = run(&["--output-format", "markdown"]);
+    assert!(content.contains("```\n 1 | line 1\n"));
+
+    let content = run(&["--output-format", "xml"]);
+    assert!(content.contains("<document_content><![CDATA[ 1 | line 1\n"));
+
+    let content = run(&["--output-format", "html"]);
+    assert!(content.contains("<pre class=\"numbered\"><code><span class=\"line\" data-line=\"1\">line 1</span>\n"));
+    assert!(content.contains("<span class=\"line\" data-line=\"13\"></span></code></pre>"));
+    assert!(content.contains("content: attr(data-line)"));
+
+    // Outlines shown to fit a budget keep the numbers of the lines in the file
+    let source: String = (0..40)
+        .map(|i| format!("fn function_{}() {{\n{}}}\n", i, "    let value = compute_something_long(1, \"padding text here\");\n".repeat(8)))
+        .collect();
+    fs::write(test_dir.join("a.txt"), "short\n").unwrap();
+    fs::write(test_dir.join("big.rs"), source).unwrap();
+    let content = run(&["--max-tokens", "1200"]);
+    assert!(content.contains("[File Begins] big.rs\n  1 | fn function_0() {\n 11 | fn function_1() {\n"));
+    assert!(content.contains("391 | fn function_39() {\n[outline to fit the token budget: 40 of 400 lines shown]\n[File Ends] big.rs"));
+}
+
+#[test]
+fn test_file_selectors() {
+    let temp_dir = tempdir().unwrap();
+    let lib = temp_dir.path().join("lib.rs");
+    fs::write(&lib, "use std::io;\n\n/// Adds one.\n#[inline]\nfn add_one(x: u32) -> u32 {\n    let c = '{';\n    x + 1\n}\n\nfn other() {}\n").unwrap();
+    let script = temp_dir.path().join("tool.py");
+    fs::write(&script, "import os\n\nclass Tool:\n    def run(self):\n        return 1\n\n    def stop(self):\n        pass\n").unwrap();
+    let output_path = temp_dir.path().join("output.txt");
+
+    let run = |args: &[String]| {
+        Command::cargo_bin("repo2txt")
+            .unwrap()
+            .arg("--output-file")
+            .arg(&output_path)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let selector = |path: &std::path::PathBuf, selection: &str| format!("{}{}", path.display(), selection);
+    let lib_name = lib.display().to_string();
+
+    // Symbols take their doc comments and attributes along; the braces in
+    // character literals do not end the function early
+    let output = run(&["-f".into(), selector(&lib, "#add_one"), "-f".into(), selector(&lib, ":1"), "-f".into(), selector(&script, "#Tool.run")]);
+    assert!(output.status.success());
+    let content = fs::read_to_string(&output_path).unwrap();
+    println!("Output content: {}", content);
+    assert!(content.contains(&format!(
+        "[File Begins] {}\n1 | use std::io;\n[line 2 omitted]\n3 | /// Adds one.\n4 | #[inline]\n5 | fn add_one(x: u32) -> u32 {{\n6 |     let c = '{{';\n7 |     x + 1\n8 | }}\n[lines 9-10 omitted]\n[File Ends] {}",
+        lib_name, lib_name,
+    )));
+    assert!(content.contains("[lines 1-3 omitted]\n4 |     def run(self):\n5 |         return 1\n[lines 6-8 omitted]\n"));
+
+    // Overlapping ranges are merged; whole files are numbered only with --line-numbers
+    let output = run(&["-f".into(), selector(&script, ":3-5"), "-f".into(), selector(&script, ":4-"), "-f".into(), lib_name.clone()]);
+    assert!(output.status.success());
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(content.contains("[lines 1-2 omitted]\n3 | class Tool:\n4 |     def run(self):\n"));
+    assert!(content.contains("8 |         pass\n[File Ends]"));
+    assert!(content.contains("use std::io;\n\n/// Adds one.\n"));
+
+    let output = run(&["-f".into(), selector(&script, "#Tool::stop"), "--output-format".into(), "json".into()]);
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&output_path).unwrap()).unwrap();
+    assert_eq!(json["files"][0]["ranges"], serde_json::json!([[7, 8]]));
+    assert_eq!(json["files"][0]["content"], "[lines 1-6 omitted]\n    def stop(self):\n        pass\n");
+
+    // A lone whole file is still written without markers
+    let output = run(&["-f".into(), lib_name.clone()]);
+    assert!(output.status.success());
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(content.contains("use std::io;\n"));
+    assert!(!content.contains(&format!("[File Begins] {}", lib_name)));
+
+    let output = run(&["-f".into(), selector(&lib, "#missing")]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Symbol 'missing' was not found"));
+    let output = run(&["-f".into(), selector(&lib, ":50-60")]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Line 50 is past the end"));
+}
+
+#[test]
+fn test_include_patterns() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_include");
+    fs::create_dir_all(test_dir.join("src/generated")).unwrap();
+    fs::create_dir_all(test_dir.join("docs")).unwrap();
+    fs::write(test_dir.join("src/main.rs"), "fn main() {}").unwrap();
+    fs::write(test_dir.join("src/generated/schema.rs"), "// generated").unwrap();
+    fs::write(test_dir.join("src/helper.py"), "print('helper')").unwrap();
+    fs::write(test_dir.join("docs/guide.md"), "# Guide").unwrap();
+    fs::write(test_dir.join("Cargo.toml"), "[package]").unwrap();
+    let output_path = temp_dir.path().join("output.txt");
+
+    let run = |args: &[&str]| {
+        Command::cargo_bin("repo2txt")
+            .unwrap()
+            .arg("--repo-path")
+            .arg(&test_dir)
+            .arg("--output-file")
+            .arg(&output_path)
+            .arg("--use-gitignore=false")
+            .args(args)
+            .assert()
+            .success();
+        fs::read_to_string(&output_path).unwrap()
+    };
+
+    // The last matching pattern wins, and directories left empty are not shown
+    let content = run(&["--include", "src/**/*.rs", "--include", "Cargo.toml", "--include", "!src/generated/**"]);
+    println!("Output content: {}", content);
+    assert!(content.contains("[File Begins] src/main.rs"));
+    assert!(content.contains("[File Begins] Cargo.toml"));
+    assert!(!content.contains("schema.rs"));
+    assert!(!content.contains("helper.py"));
+    assert!(!content.contains("docs"));
+    assert!(!content.contains("generated"));
+
+    // A later pattern can take back an earlier negation
+    let content = run(&["--include", "src/**", "--include", "!src/generated/**", "--include", "src/generated/schema.rs"]);
+    assert!(content.contains("[File Begins] src/generated/schema.rs"));
+    assert!(content.contains("[File Begins] src/helper.py"));
+    assert!(!content.contains("Cargo.toml"));
+
+    // Starting with a negation keeps everything else
+    let content = run(&["--include", "!docs/**"]);
+    assert!(content.contains("[File Begins] src/helper.py"));
+    assert!(content.contains("[File Begins] Cargo.toml"));
+    assert!(!content.contains("guide.md"));
+
+    // The ignore options still apply to included files
+    let content = run(&["--include", "src/**", "--ignore-types", "py"]);
+    assert!(content.contains("[File Begins] src/main.rs"));
+    assert!(!content.contains("helper.py"));
+
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--use-gitignore=false")
+        .args(["--include", "src/**/*.rs", "--include", "!src/generated/**", "--explain"])
+        .output()
+        .unwrap();
+    let explained = String::from_utf8_lossy(&output.stdout);
+    assert!(explained.contains("src/generated/schema.rs: excluded: --include pattern \"!src/generated/**\""));
+    assert!(explained.contains("Cargo.toml: excluded: matched by no --include pattern"));
+}
+
+#[test]
+fn test_multiple_include_dirs() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_include_dirs");
+    fs::create_dir_all(test_dir.join("crates/core/src")).unwrap();
+    fs::create_dir_all(test_dir.join("crates/cli")).unwrap();
+    fs::create_dir_all(test_dir.join("docs")).unwrap();
+    fs::write(test_dir.join("crates/core/src/lib.rs"), "pub fn core() {}").unwrap();
+    fs::write(test_dir.join("crates/core/Cargo.toml"), "[package]").unwrap();
+    fs::write(test_dir.join("crates/cli/main.rs"), "fn main() {}").unwrap();
+    fs::write(test_dir.join("crates/README.md"), "crates").unwrap();
+    fs::write(test_dir.join("docs/guide.md"), "# Guide").unwrap();
+    fs::write(test_dir.join("top.txt"), "top").unwrap();
+    let output_path = temp_dir.path().join("output.txt");
+
+    // Include dirs are relative to the repository, not to the working directory
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .current_dir(temp_dir.path())
+        .arg("--repo-path")
+        .arg("test_include_dirs")
+        .arg("--output-file")
+        .arg(&output_path)
+        .arg("--use-gitignore=false")
+        .args(["--include-dir", "./crates/core/src", "--include-dir", "docs/"])
+        .output()
+        .unwrap();
+    println!("Command stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(output.status.success());
+
+    let content = fs::read_to_string(&output_path).unwrap();
+    println!("Output content: {}", content);
+    // Only the directories leading to the included ones appear in the tree
+    assert!(content.contains("test_include_dirs\n├── crates\n│   └── core\n│       └── src\n│           └── lib.rs\n└── docs\n    └── guide.md\n"));
+    assert!(content.contains("[File Begins] crates/core/src/lib.rs"));
+    assert!(content.contains("[File Begins] docs/guide.md"));
+    assert!(!content.contains("Cargo.toml"));
+    assert!(!content.contains("README.md"));
+    assert!(!content.contains("top.txt"));
+
+    let output = Command::cargo_bin("repo2txt")
+        .unwrap()
+        .arg("--repo-path")
+        .arg(&test_dir)
+        .arg("--output-file")
+        .arg(&output_path)
+        .args(["--include-dir", "missing"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("The include directory does not exist or is not a directory: missing"));
+}
+
+#[test]
+fn test_glob_patterns_match_relative_paths() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_globs");
+    fs::create_dir_all(test_dir.join("src/nested")).unwrap();
+    fs::create_dir_all(test_dir.join("docs")).unwrap();
+    fs::create_dir_all(test_dir.join("lib/docs")).unwrap();
+    fs::write(test_dir.join("src/top.rs"), "fn top() {}").unwrap();
+    fs::write(test_dir.join("src/nested/deep.rs"), "fn deep() {}").unwrap();
+    fs::write(test_dir.join("docs/root.md"), "root docs").unwrap();
+    fs::write(test_dir.join("lib/docs/lib.md"), "lib docs").unwrap();
+    fs::write(test_dir.join("NOTES.TXT"), "notes").unwrap();
+
+    // The default repository path "." is walked from inside the repository
+    let run = |args: &[&str]| {
+        let output = Command::cargo_bin("repo2txt")
+            .unwrap()
+            .current_dir(&test_dir)
+            .arg("--output-file")
+            .arg("-")
+            .arg("--use-gitignore=false")
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let content = run(&[]);
+    println!("Output content: {}", content);
+    assert!(content.contains("[File Begins] src/nested/deep.rs"));
+    assert!(content.contains("[File Begins] NOTES.TXT"));
+
+    // `*` stops at `/`, `/` anchors at the root, names match at any depth
+    let content = run(&["--ignore-files", "src/*.rs,/docs"]);
+    assert!(!content.contains("[File Begins] src/top.rs"));
+    assert!(content.contains("[File Begins] src/nested/deep.rs"));
+    assert!(!content.contains("root.md"));
+    assert!(content.contains("[File Begins] lib/docs/lib.md"));
+
+    let content = run(&["--ignore-files", "src/**/*.rs,docs/"]);
+    assert!(!content.contains(".rs"));
+    assert!(!content.contains("root.md"));
+    assert!(!content.contains("lib.md"));
+
+    // Matching is case-sensitive unless asked otherwise
+    let content = run(&["--ignore-files", "*.txt"]);
+    assert!(content.contains("[File Begins] NOTES.TXT"));
+    let content = run(&["--ignore-files", "*.txt", "--glob-case-insensitive"]);
+    assert!(!content.contains("NOTES.TXT"));
+
+    let content = run(&["--include", "/docs", "--include", "*.rs"]);
+    assert!(content.contains("[File Begins] docs/root.md"));
+    assert!(content.contains("[File Begins] src/nested/deep.rs"));
+    assert!(!content.contains("lib.md"));
+}
+
+#[test]
+fn test_exclude_dir_paths() {
+    let temp_dir = tempdir().unwrap();
+    let test_dir = temp_dir.path().join("test_exclude_paths");
+    for dir in ["build", "src/build", "crates/legacy/generated", "crates/core/generated"] {
+        fs::create_dir_all(test_dir.join(dir)).unwrap();
+        fs::write(test_dir.join(dir).join("file.txt"), dir).unwrap();
+    }
+    let output_path = temp_dir.path().join("output.txt");
+
+    let run = |exclude_dir: &str| {
+        Command::cargo_bin("repo2txt")
+            .unwrap()
+            .arg("--repo-path")
+            .arg(&test_dir)
+            .arg("--output-file")
+            .arg(&output_path)
+            .arg("--use-gitignore=false")
+            .arg("--exclude-dir")
+            .arg(exclude_dir)
+            .assert()
+            .success();
+        fs::read_to_string(&output_path).unwrap()
+    };
+
+    // Bare names exclude directories anywhere
+    let content = run("build");
+    println!("Output content: {}", content);
+    assert!(!content.contains("[File Begins] build/file.txt"));
+    assert!(!content.contains("[File Begins] src/build/file.txt"));
+    assert!(content.contains("[File Begins] crates/legacy/generated/file.txt"));
+
+    // Entries with a slash are paths from the repository root
+    let content = run("/build,crates/legacy/generated");
+    assert!(!content.contains("[File Begins] build/file.txt"));
+    assert!(content.contains("[File Begins] src/build/file.txt"));
+    assert!(!content.contains("legacy/generated/file.txt"));
+    assert!(content.contains("[File Begins] crates/core/generated/file.txt"));
+
+    // ... or globs
+    let content = run("crates/*/generated");
+    assert!(!content.contains("generated/file.txt"));
+    assert!(content.contains("[File Begins] build/file.txt"));
+}